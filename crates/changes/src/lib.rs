@@ -19,6 +19,51 @@ pub struct ChangeDetail {
     pub detail_type: ChangeDetailType,
 }
 
+/// blake3 digest of the file contents
+pub fn get_file_digest(path: &std::path::Path) -> anyhow::Result<Arc<str>> {
+    let contents = std::fs::read(path).context(format_context!("failed to load {path:?}"))?;
+    Ok(blake3::hash(&contents).to_string().into())
+}
+
+/// blake3 digest of the files in a directory tree. Files are hashed in path order
+/// using their path relative to `path` and their content digest. If `globs` is
+/// provided, only relative paths that match are included.
+pub fn get_directory_digest(
+    path: &std::path::Path,
+    globs: Option<&HashSet<Arc<str>>>,
+) -> anyhow::Result<Arc<str>> {
+    if !path.is_dir() {
+        return Err(anyhow_source_location::format_error!(
+            "{path:?} is not a directory"
+        ));
+    }
+
+    let mut hasher = blake3::Hasher::new();
+    for entry in walkdir::WalkDir::new(path).sort_by_file_name() {
+        let entry = entry.context(format_context!("failed to walk {path:?}"))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let relative_path = entry
+            .path()
+            .strip_prefix(path)
+            .unwrap_or(entry.path())
+            .to_string_lossy()
+            .replace('\\', "/");
+        if let Some(globs) = globs {
+            if !glob::match_globs(globs, relative_path.as_str()) {
+                continue;
+            }
+        }
+
+        hasher.update(relative_path.as_bytes());
+        hasher.update(get_file_digest(entry.path())?.as_bytes());
+    }
+
+    Ok(hasher.finalize().to_string().into())
+}
+
 fn changes_logger(progress: &mut printer::MultiProgressBar) -> logger::Logger {
     logger::Logger::new_progress(progress, "Changes".into())
 }
@@ -36,6 +81,14 @@ impl Changes {
         !skip_folders.contains(&file_name)
     }
 
+    /// True if any folder in `path` is one of `skip_folders`
+    fn is_in_skip_folders(path: &str, skip_folders: &[Arc<str>]) -> bool {
+        std::path::Path::new(path).components().any(|component| {
+            let name = component.as_os_str().to_string_lossy();
+            skip_folders.iter().any(|folder| folder.as_ref() == name)
+        })
+    }
+
     fn process_entry(
         progress: &mut printer::MultiProgressBar,
         path: &std::path::Path,
@@ -43,10 +96,7 @@ impl Changes {
         progress.set_message(format!("Processing {path:?}").as_str());
 
         let detail_type = if path.is_file() {
-            let contents =
-                std::fs::read(path).context(format_context!("failed to load {path:?}"))?;
-            let hash = blake3::hash(&contents);
-            ChangeDetailType::File(hash.to_string().into())
+            ChangeDetailType::File(get_file_digest(path)?)
         } else if path.is_dir() {
             ChangeDetailType::Directory
         } else {
@@ -149,7 +199,15 @@ impl Changes {
         &mut self,
         progress: &mut printer::MultiProgressBar,
         inputs: &HashSet<Arc<str>>,
+        rule_skip_folders: &[Arc<str>],
     ) -> anyhow::Result<()> {
+        // the rule's folders are only skipped for this walk
+        let skip_folders: Vec<Arc<str>> = self
+            .skip_folders
+            .iter()
+            .chain(rule_skip_folders)
+            .cloned()
+            .collect();
         for input in inputs {
             changes_logger(progress).trace(
                 format!("Update changes for {input}").as_str(),
@@ -188,7 +246,7 @@ impl Changes {
                 let walk_dir: Vec<_> = walkdir::WalkDir::new(glob_include_path.as_ref())
                     .into_iter()
                     .filter_entry(|e| {
-                        Self::filter_update(e, &self.entries, &self.skip_folders, inputs)
+                        Self::filter_update(e, &self.entries, &skip_folders, inputs)
                     })
                     .filter_map(|entry| entry.ok())
                     .collect();
@@ -224,27 +282,40 @@ impl Changes {
         input.strip_prefix("./").unwrap_or(input)
     }
 
+    /// Returns the sorted list of files that match `globs` and contribute to the digest
+    pub fn inspect_inputs(
+        &self,
+        globs: &HashSet<Arc<str>>,
+        skip_folders: &[Arc<str>],
+    ) -> Vec<Arc<str>> {
+        let mut inputs: Vec<Arc<str>> = self
+            .entries
+            .iter()
+            .filter(|(path, change_detail)| {
+                matches!(change_detail.detail_type, ChangeDetailType::File(_))
+                    && glob::match_globs(globs, Self::sanitize_path(path))
+                    && !Self::is_in_skip_folders(path, skip_folders)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        inputs.sort();
+        inputs
+    }
+
     pub fn get_digest(
         &self,
         progress: &mut printer::MultiProgressBar,
         seed: &str,
         globs: &HashSet<Arc<str>>,
+        skip_folders: &[Arc<str>],
     ) -> anyhow::Result<Arc<str>> {
-        let mut inputs = Vec::new();
-        for path in self.entries.keys() {
-            let sane_path = Self::sanitize_path(path);
-            if glob::match_globs(globs, sane_path) {
-                inputs.push(path);
-            }
-        }
-
-        inputs.sort();
+        let inputs = self.inspect_inputs(globs, skip_folders);
 
         let mut count = 0usize;
         let mut hasher = blake3::Hasher::new();
         hasher.update(seed.as_bytes());
         for input in inputs.iter() {
-            if let Some(change_detail) = self.entries.get(*input) {
+            if let Some(change_detail) = self.entries.get(input) {
                 if let ChangeDetailType::File(hash) = &change_detail.detail_type {
                     changes_logger(progress).trace( 
                         format!("Hashing {input}:{hash}").as_str(),
@@ -293,4 +364,12 @@ mod tests {
         let result = add(2, 2);
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn skip_folders_match_any_path_component() {
+        let skip_folders: Vec<Arc<str>> = vec!["target".into()];
+        assert!(Changes::is_in_skip_folders("./tools/target/debug/app", &skip_folders));
+        assert!(!Changes::is_in_skip_folders("./tools/targets/app", &skip_folders));
+        assert!(!Changes::is_in_skip_folders("./tools/app", &[]));
+    }
 }
@@ -1,9 +1,41 @@
 use anyhow::Context;
-use anyhow_source_location::format_context;
+use anyhow_source_location::{format_context, format_error};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Parses `KEY=VALUE` lines of a dotenv file. Blank lines and `#` comments are
+/// skipped, `export ` prefixes are allowed and values can be wrapped in quotes.
+pub fn parse_dotenv(content: &str) -> anyhow::Result<Vec<(Arc<str>, Arc<str>)>> {
+    let mut result = Vec::new();
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line.split_once('=').ok_or(format_error!(
+            "line {}: expected KEY=VALUE, got `{line}`",
+            line_number + 1
+        ))?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format_error!(
+                "line {}: `{key}` is not a valid variable name",
+                line_number + 1
+            ));
+        }
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|e| e.strip_suffix('"'))
+            .or_else(|| value.strip_prefix('\'').and_then(|e| e.strip_suffix('\'')))
+            .unwrap_or(value);
+        result.push((key.into(), value.into()));
+    }
+    Ok(result)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(deny_unknown_fields)]
 pub struct Environment {
@@ -13,6 +45,13 @@ pub struct Environment {
     pub system_paths: Option<Vec<Arc<str>>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub inherited_vars: Option<Vec<Arc<str>>>,
+    /// Names of variables whose values are redacted from the logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_vars: Option<Vec<Arc<str>>>,
+    /// Names of list variables (only `PATH` is built from a list) that keep repeated
+    /// entries. By default only the first occurrence of an entry is kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_duplicates_vars: Option<Vec<Arc<str>>>,
 }
 
 impl Environment {
@@ -20,15 +59,26 @@ impl Environment {
         self.get_path_with_system_paths()
     }
 
+    fn is_keep_duplicates(&self, var: &str) -> bool {
+        self.keep_duplicates_vars
+            .iter()
+            .flatten()
+            .any(|name| name.as_ref() == var)
+    }
+
+    /// `paths` then `system_paths` joined with `:`. Repeated directories are
+    /// dropped after their first occurrence unless `PATH` is in `keep_duplicates_vars`.
     pub fn get_path_with_system_paths(&self) -> Arc<str> {
-        let mut path = self.paths.join(":");
-        if let Some(system_paths) = &self.system_paths {
-            if !system_paths.is_empty() {
-                path.push(':');
-                path.push_str(system_paths.join(":").as_str());
-            }
-        }
-        path.into()
+        let all_paths = self.paths.iter().chain(self.system_paths.iter().flatten());
+
+        // several rules often add the same directory, the first occurrence wins
+        let mut known_paths = std::collections::HashSet::new();
+        let is_keep_duplicates = self.is_keep_duplicates("PATH");
+        let path: Vec<&str> = all_paths
+            .filter(|path| is_keep_duplicates || known_paths.insert(path.as_ref()))
+            .map(|path| path.as_ref())
+            .collect();
+        path.join(":").into()
     }
 
     pub fn get_inherited_vars(&self) -> anyhow::Result<HashMap<Arc<str>, Arc<str>>> {
@@ -74,3 +124,80 @@ impl Environment {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_strings(vars: Vec<(Arc<str>, Arc<str>)>) -> Vec<(String, String)> {
+        vars.into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_dotenv_quotes_comments_and_export() {
+        let content = r#"
+# a comment
+export PLAIN=value
+DOUBLE="quoted value"
+SINGLE='it''s'
+  SPACED = padded
+EMPTY=
+EQUALS=a=b
+MISMATCHED="open
+"#;
+        let expected = [
+            ("PLAIN", "value"),
+            ("DOUBLE", "quoted value"),
+            ("SINGLE", "it''s"),
+            ("SPACED", "padded"),
+            ("EMPTY", ""),
+            ("EQUALS", "a=b"),
+            ("MISMATCHED", "\"open"),
+        ];
+        let expected: Vec<(String, String)> = expected
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(to_strings(parse_dotenv(content).unwrap()), expected);
+    }
+
+    #[test]
+    fn parse_dotenv_rejects_malformed_lines() {
+        let error = parse_dotenv("A=1\nNO_EQUALS\n").unwrap_err();
+        assert!(format!("{error:?}").contains("line 2"));
+        assert!(parse_dotenv("=value").is_err());
+        assert!(parse_dotenv("TWO WORDS=value").is_err());
+        assert!(parse_dotenv("export =value").is_err());
+    }
+
+    #[test]
+    fn path_keeps_the_first_occurrence_by_default() {
+        let mut environment = Environment {
+            paths: vec![
+                "/ws/sysroot/bin".into(),
+                "/ws/tools".into(),
+                "/ws/sysroot/bin".into(),
+            ],
+            system_paths: Some(vec!["/usr/bin".into(), "/ws/tools".into(), "/bin".into()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            environment.get_path().as_ref(),
+            "/ws/sysroot/bin:/ws/tools:/usr/bin:/bin"
+        );
+
+        environment.keep_duplicates_vars = Some(vec!["LD_LIBRARY_PATH".into()]);
+        assert_eq!(
+            environment.get_path().as_ref(),
+            "/ws/sysroot/bin:/ws/tools:/usr/bin:/bin"
+        );
+
+        environment.keep_duplicates_vars = Some(vec!["PATH".into()]);
+        assert_eq!(
+            environment.get_path().as_ref(),
+            "/ws/sysroot/bin:/ws/tools:/ws/sysroot/bin:/usr/bin:/ws/tools:/bin"
+        );
+    }
+}
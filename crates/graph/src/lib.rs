@@ -37,24 +37,47 @@ impl Graph {
         self.directed_graph[node].as_ref()
     }
 
+    /// Returns the names of the tasks in each dependency cycle of the graph
+    pub fn get_cycles(&self) -> Vec<Vec<Arc<str>>> {
+        petgraph::algo::tarjan_scc(&self.directed_graph)
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1
+                    || self.directed_graph.contains_edge(component[0], component[0])
+            })
+            .map(|component| {
+                let mut names: Vec<Arc<str>> = component
+                    .into_iter()
+                    .map(|node| self.directed_graph[node].clone())
+                    .collect();
+                names.sort();
+                names
+            })
+            .collect()
+    }
+
     pub fn get_sorted_tasks(
         &self,
-        target: Option<Arc<str>>,
+        targets: &[Arc<str>],
     ) -> anyhow::Result<Vec<petgraph::prelude::NodeIndex>> {
-        let sorted_tasks = if let Some(target) = target {
-            let target_node = self
-                .directed_graph
-                .node_indices()
-                .find(|&node| {
-                    let value = &self.directed_graph[node];
-                    value.as_ref() == target.as_ref()
-                })
-                .ok_or(format_error!("Target not found: {target}"))?;
-
+        let sorted_tasks = if !targets.is_empty() {
             let mut tasks: Vec<petgraph::prelude::NodeIndex> = Vec::new();
-            let mut dfs = petgraph::visit::DfsPostOrder::new(&self.directed_graph, target_node);
-            while let Some(node) = dfs.next(&self.directed_graph) {
-                tasks.push(node);
+            // the finished map is shared so targets with common dependencies visit them once
+            let mut dfs = petgraph::visit::DfsPostOrder::empty(&self.directed_graph);
+            for target in targets {
+                let target_node = self
+                    .directed_graph
+                    .node_indices()
+                    .find(|&node| {
+                        let value = &self.directed_graph[node];
+                        value.as_ref() == target.as_ref()
+                    })
+                    .ok_or(format_error!("Target not found: {target}"))?;
+
+                dfs.move_to(target_node);
+                while let Some(node) = dfs.next(&self.directed_graph) {
+                    tasks.push(node);
+                }
             }
             tasks
         } else {
@@ -67,3 +90,23 @@ impl Graph {
         Ok(sorted_tasks)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_cycles_names_tasks() {
+        let mut graph = Graph::default();
+        for task in ["a", "b", "c", "d"] {
+            graph.add_task(task.into());
+        }
+        graph.add_dependency("a", "b").unwrap();
+        graph.add_dependency("b", "a").unwrap();
+        graph.add_dependency("c", "d").unwrap();
+        assert_eq!(graph.get_cycles(), vec![vec![Arc::from("a"), Arc::from("b")]]);
+
+        graph.add_dependency("d", "d").unwrap();
+        assert_eq!(graph.get_cycles().len(), 2);
+    }
+}
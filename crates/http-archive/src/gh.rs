@@ -1,5 +1,5 @@
 use anyhow::Context;
-use anyhow_source_location::format_context;
+use anyhow_source_location::{format_context, format_error};
 use std::sync::Arc;
 
 fn gh_logger(progress: &mut printer::MultiProgressBar) -> logger::Logger<'_> {
@@ -58,6 +58,49 @@ pub fn transform_url_to_arguments(
     ])
 }
 
+// `gh release download --output` is available starting with 2.19
+const MIN_GH_VERSION: (u64, u64) = (2, 19);
+
+/// Parses the major and minor version from `gh version 2.40.1 (2023-12-13)`
+fn parse_version(output: &str) -> Option<(u64, u64)> {
+    let version = output.strip_prefix("gh version ")?.split_whitespace().next()?;
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Checks that `gh_command` exists and is new enough to download release assets
+pub fn check_version(gh_command: &str) -> anyhow::Result<()> {
+    const HINT: &str = "run `spaces checkout --force-install-tools` or set `allow_gh = false` in .spaces/config.toml to download with http";
+    let output = std::process::Command::new(gh_command)
+        .arg("--version")
+        .output()
+        .map_err(|error| format_error!("Failed to run {gh_command}: {error}. {HINT}"))?;
+    if !output.status.success() {
+        return Err(format_error!(
+            "{gh_command} --version exited with {}. {HINT}",
+            output.status
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = parse_version(stdout.as_ref()).ok_or(format_error!(
+        "Failed to parse the version of {gh_command} from `{}`",
+        stdout.trim()
+    ))?;
+    if version < MIN_GH_VERSION {
+        return Err(format_error!(
+            "{gh_command} is version {}.{}, spaces needs at least {}.{}. {HINT}",
+            version.0,
+            version.1,
+            MIN_GH_VERSION.0,
+            MIN_GH_VERSION.1
+        ));
+    }
+    Ok(())
+}
+
 pub fn download(gh_command: &str, url: &str, arguments: Vec<Arc<str>>, progress_bar: &mut printer::MultiProgressBar) -> anyhow::Result<()> {
     let options = printer::ExecuteOptions {
         arguments,
@@ -75,4 +118,16 @@ pub fn download(gh_command: &str, url: &str, arguments: Vec<Arc<str>>, progress_
         ))?;
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_gh_version() {
+        let output = "gh version 2.40.1 (2023-12-13)\nhttps://github.com";
+        assert_eq!(parse_version(output), Some((2, 40)));
+        assert_eq!(parse_version("git version 2.40.1"), None);
+        assert!(parse_version("gh version 2.9.0").unwrap() < MIN_GH_VERSION);
+    }
+}
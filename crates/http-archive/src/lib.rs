@@ -1,4 +1,5 @@
 mod gh;
+mod netrc;
 
 use anyhow::Context;
 use anyhow_source_location::{format_context, format_error};
@@ -7,7 +8,45 @@ use std::collections::HashSet;
 use std::sync::{Arc, RwLock};
 use tokio::io::AsyncWriteExt;
 
-struct State {}
+const DEFAULT_MAX_CONCURRENT_DOWNLOADS: usize = 4;
+
+/// Token bucket shared by all downloads. Up to one second of bytes can be sent as a burst,
+/// chunks beyond that wait until the bucket has refilled.
+struct RateLimiter {
+    bytes_per_second: f64,
+    available: f64,
+    last_refill: std::time::Instant,
+}
+
+impl RateLimiter {
+    fn new(bytes_per_second: u64, now: std::time::Instant) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second as f64,
+            available: bytes_per_second as f64,
+            last_refill: now,
+        }
+    }
+
+    /// Takes `bytes` from the bucket and returns how long to wait before continuing
+    fn consume(&mut self, bytes: usize, now: std::time::Instant) -> std::time::Duration {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+        self.available =
+            (self.available + elapsed * self.bytes_per_second).min(self.bytes_per_second);
+        self.available -= bytes as f64;
+        if self.available >= 0.0 {
+            std::time::Duration::ZERO
+        } else {
+            std::time::Duration::from_secs_f64(-self.available / self.bytes_per_second)
+        }
+    }
+}
+
+struct State {
+    is_offline: bool,
+    max_concurrent_downloads: usize,
+    rate_limiter: Option<RateLimiter>,
+}
 
 static STATE: state::InitCell<RwLock<State>> = state::InitCell::new();
 
@@ -16,15 +55,81 @@ fn get_state() -> &'static RwLock<State> {
         return state;
     }
 
-    STATE.set(RwLock::new(State {}));
+    STATE.set(RwLock::new(State {
+        is_offline: false,
+        max_concurrent_downloads: DEFAULT_MAX_CONCURRENT_DOWNLOADS,
+        rate_limiter: None,
+    }));
     STATE.get()
 }
 
+// all reqwest downloads share one runtime, the semaphore caps how many run at the same time
+static RUNTIME: state::InitCell<tokio::runtime::Runtime> = state::InitCell::new();
+static DOWNLOAD_SLOTS: state::InitCell<tokio::sync::Semaphore> = state::InitCell::new();
+
+fn get_runtime() -> anyhow::Result<&'static tokio::runtime::Runtime> {
+    if let Some(runtime) = RUNTIME.try_get() {
+        return Ok(runtime);
+    }
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context(format_context!("Failed to create runtime"))?;
+    RUNTIME.set(runtime);
+    Ok(RUNTIME.get())
+}
+
+fn get_download_slots() -> &'static tokio::sync::Semaphore {
+    if let Some(slots) = DOWNLOAD_SLOTS.try_get() {
+        return slots;
+    }
+    let count = get_state().read().unwrap().max_concurrent_downloads;
+    DOWNLOAD_SLOTS.set(tokio::sync::Semaphore::new(count));
+    DOWNLOAD_SLOTS.get()
+}
+
+/// Limits the combined rate of reqwest downloads in bytes per second (default is unlimited).
+/// The limit is soft, a chunk is written before waiting for the bucket to refill.
+pub fn set_download_rate_limit(bytes_per_second: Option<u64>) {
+    let mut state = get_state().write().unwrap();
+    state.rate_limiter = bytes_per_second
+        .filter(|bytes_per_second| *bytes_per_second > 0)
+        .map(|bytes_per_second| RateLimiter::new(bytes_per_second, std::time::Instant::now()));
+}
+
+fn get_download_delay(bytes: usize) -> std::time::Duration {
+    let mut state = get_state().write().unwrap();
+    match state.rate_limiter.as_mut() {
+        Some(rate_limiter) => rate_limiter.consume(bytes, std::time::Instant::now()),
+        None => std::time::Duration::ZERO,
+    }
+}
+
+/// Sets how many archives are downloaded at the same time with reqwest or gh (default is 4).
+/// Must be called before the first download.
+pub fn set_max_concurrent_downloads(count: usize) {
+    let mut state = get_state().write().unwrap();
+    state.max_concurrent_downloads = count.max(1);
+}
+
+/// When enabled, archives that are not in the store fail instead of downloading
+pub fn set_is_offline(is_offline: bool) {
+    let mut state = get_state().write().unwrap();
+    state.is_offline = is_offline;
+}
+
+fn get_is_offline() -> bool {
+    get_state().read().unwrap().is_offline
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub enum ArchiveLink {
     None,
     #[default]
     Hard,
+    /// Links the whole extracted directory instead of each file. Workspaces share the
+    /// store copy so it must be treated as read-only.
+    Symlink,
 }
 
 fn label_logger<'a>(
@@ -46,6 +151,24 @@ pub struct Archive {
     pub strip_prefix: Option<Arc<str>>,
     pub add_prefix: Option<Arc<str>>,
     pub filename: Option<Arc<str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remap: Option<Vec<ArchiveRemap>>,
+    /// When false, the downloaded file is placed in the workspace as is (default is true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extract: Option<bool>,
+    /// When false, GitHub release urls are downloaded with http instead of gh (default is true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_gh: Option<bool>,
+}
+
+/// Remaps files matching `glob` to a destination independent of the
+/// archive level `strip_prefix` and `add_prefix`. The first matching entry wins.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ArchiveRemap {
+    pub glob: Arc<str>,
+    pub strip_prefix: Option<Arc<str>>,
+    pub add_prefix: Option<Arc<str>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -66,15 +189,29 @@ pub fn download(
     let url = url.to_string();
 
     let join_handle = runtime.spawn(async move {
+        progress.set_message("Waiting for a download slot");
+        let _download_slot = get_download_slots()
+            .acquire()
+            .await
+            .context(format_context!("Failed to acquire a download slot for {url}"))?;
+
         let client = reqwest::ClientBuilder::new()
             .redirect(reqwest::redirect::Policy::limited(20))
             .build()?;
 
-        let request = client
+        let mut request = client
             .get(&url)
             .header(reqwest::header::USER_AGENT, "wget")
             .header(reqwest::header::ACCEPT, "*/*");
 
+        if let Some(credentials) = netrc::get_credentials(&url) {
+            // reqwest marks the header as sensitive, the password is also redacted in logs
+            logger::add_secret(credentials.password.as_ref());
+            label_logger(&mut progress, &url)
+                .debug(format!("Using .netrc credentials for {}", credentials.login).as_str());
+            request = request.basic_auth(credentials.login, Some(credentials.password));
+        }
+
         label_logger(&mut progress, &url).debug(format!("Reqwest request: {request:?}").as_str());
 
         let mut response = request.send().await?;
@@ -96,6 +233,11 @@ pub fn download(
         while let Some(chunk) = response.chunk().await? {
             progress.increment(chunk.len() as u64);
             output_file.write_all(&chunk).await?;
+
+            let delay = get_download_delay(chunk.len());
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
         }
 
         Ok(progress)
@@ -106,6 +248,9 @@ pub fn download(
 
 // TODO Add a version of this that uses GH
 pub fn download_string(url: &str) -> anyhow::Result<Arc<str>> {
+    if get_is_offline() {
+        return Err(format_error!("--offline: {url} needs to be downloaded"));
+    }
     let response =
         reqwest::blocking::get(url).context(format_context!("Failed to download {url}"))?;
     let content = response
@@ -168,9 +313,11 @@ impl HttpArchive {
                 format_context!("Failed to get driver for {archive_file_name}"),
             );
 
+        // without a driver the file is moved to the extracted files unchanged
+        let is_extract = archive.extract.unwrap_or(true);
         let mut archive_driver = None;
         let full_path_to_archive = match archive_driver_result {
-            Ok(driver) => {
+            Ok(driver) if is_extract => {
                 archive_driver = Some(driver);
                 format!(
                     "{}/{}.{}",
@@ -179,8 +326,9 @@ impl HttpArchive {
                     driver.extension()
                 )
             }
-            Err(_) => {
-                format!("{full_path_to_archive}/{archive_file_name}")
+            _ => {
+                // the sha256 is part of the path so a new checksum is downloaded again
+                format!("{full_path_to_archive}/{effective_sha256}/{archive_file_name}")
             }
         };
 
@@ -192,7 +340,7 @@ impl HttpArchive {
             archive_driver,
             full_path_to_archive,
             spaces_key: spaces_key.to_string(),
-            allow_gh_for_download: true,
+            allow_gh_for_download: archive.allow_gh.unwrap_or(true),
             tools_path: tools_path.to_owned(),
         })
     }
@@ -201,6 +349,11 @@ impl HttpArchive {
         self.allow_gh_for_download = value;
     }
 
+    /// The sha256 of the archive, downloaded by `new` if the archive has a url to it
+    pub fn get_sha256(&self) -> Arc<str> {
+        self.archive.sha256.clone()
+    }
+
     pub fn get_path_to_extracted_files(&self) -> String {
         format!("{}_files", self.full_path_to_archive)
     }
@@ -223,6 +376,14 @@ impl HttpArchive {
         workspace_directory: &str,
         space_directory: &str,
     ) -> anyhow::Result<()> {
+        if self.archive.link == ArchiveLink::Symlink {
+            return self.create_directory_link(
+                &mut progress_bar,
+                workspace_directory,
+                space_directory,
+            );
+        }
+
         //construct a list of files to link
         let mut files = Vec::new();
         let all_files = self
@@ -239,17 +400,11 @@ impl HttpArchive {
             }
         }
 
-        let target_prefix = if let Some(add_prefix) = self.archive.add_prefix.as_ref() {
-            if add_prefix.starts_with("//") {
-                format!("{workspace_directory}/{add_prefix}").into()
-            } else if add_prefix.starts_with('/') {
-                add_prefix.clone()
-            } else {
-                format!("{workspace_directory}/{add_prefix}").into()
-            }
-        } else {
-            format!("{workspace_directory}/{space_directory}").into()
-        };
+        let target_prefix = Self::get_target_prefix(
+            self.archive.add_prefix.as_ref(),
+            workspace_directory,
+            space_directory,
+        );
 
         progress_bar.set_total(files.len() as u64);
 
@@ -257,12 +412,31 @@ impl HttpArchive {
             let source = format!("{}/{}", self.get_path_to_extracted_files(), file);
 
             progress_bar.set_message(file.as_ref());
-            let relative_target_path =
-                if let Some(strip_prefix) = self.archive.strip_prefix.as_ref() {
-                    file.strip_prefix(strip_prefix.as_ref())
-                } else {
-                    Some(file.as_ref())
-                };
+
+            let remap = self.archive.remap.as_ref().and_then(|remap| {
+                remap
+                    .iter()
+                    .find(|entry| glob_match::glob_match(entry.glob.as_ref(), file.as_ref()))
+            });
+
+            let (target_prefix, strip_prefix) = if let Some(remap) = remap {
+                (
+                    Self::get_target_prefix(
+                        remap.add_prefix.as_ref(),
+                        workspace_directory,
+                        space_directory,
+                    ),
+                    remap.strip_prefix.as_ref(),
+                )
+            } else {
+                (target_prefix.clone(), self.archive.strip_prefix.as_ref())
+            };
+
+            let relative_target_path = if let Some(strip_prefix) = strip_prefix {
+                file.strip_prefix(strip_prefix.as_ref())
+            } else {
+                Some(file.as_ref())
+            };
 
             if let Some(relative_target_path) = relative_target_path {
                 let full_target_path = format!("{}/{}", target_prefix, relative_target_path);
@@ -276,14 +450,11 @@ impl HttpArchive {
                             format_context!("hard link {full_target_path} -> {source}",),
                         )?;
                     }
-                    ArchiveLink::None => (),
+                    ArchiveLink::None | ArchiveLink::Symlink => (),
                 }
             } else {
                 label_logger(&mut progress_bar, "hardlink").warning(
-                    format!(
-                        "Failed to strip prefix {:?} from {file}",
-                        self.archive.strip_prefix
-                    )
+                    format!("Failed to strip prefix {strip_prefix:?} from {file}")
                     .as_str(),
                 );
             }
@@ -293,6 +464,82 @@ impl HttpArchive {
         Ok(())
     }
 
+    /// Replaces the target directory with a symlink to the extracted files
+    /// (below `strip_prefix`) in the store.
+    fn create_directory_link(
+        &self,
+        progress_bar: &mut printer::MultiProgressBar,
+        workspace_directory: &str,
+        space_directory: &str,
+    ) -> anyhow::Result<()> {
+        if self.archive.globs.is_some() || self.archive.remap.is_some() {
+            return Err(format_error!(
+                "`globs` and `remap` select individual files and can't be used with Symlink links"
+            ));
+        }
+
+        let target_prefix = Self::get_target_prefix(
+            self.archive.add_prefix.as_ref(),
+            workspace_directory,
+            space_directory,
+        );
+        let source = if let Some(strip_prefix) = self.archive.strip_prefix.as_ref() {
+            format!(
+                "{}/{}",
+                self.get_path_to_extracted_files(),
+                strip_prefix.trim_end_matches('/')
+            )
+        } else {
+            self.get_path_to_extracted_files()
+        };
+
+        let target = std::path::Path::new(target_prefix.as_ref());
+        if target.is_symlink() {
+            std::fs::remove_file(target)
+                .context(format_context!("failed to remove symlink {target_prefix}"))?;
+        } else if target.exists() {
+            return Err(format_error!(
+                "{target_prefix} already exists and is not a symlink, remove it to link {source}"
+            ));
+        } else if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .context(format_context!("failed to create {parent:?}"))?;
+        }
+
+        label_logger(progress_bar, "symlink")
+            .trace(format!("Creating symlink {target_prefix} -> {source}").as_str());
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(source.as_str(), target).context(format_context!(
+            "failed to create symlink {target_prefix} -> {source}"
+        ))?;
+
+        #[cfg(windows)]
+        std::os::windows::fs::symlink_dir(source.as_str(), target).context(format_context!(
+            "failed to create symlink {target_prefix} -> {source}"
+        ))?;
+
+        Ok(())
+    }
+
+    fn get_target_prefix(
+        add_prefix: Option<&Arc<str>>,
+        workspace_directory: &str,
+        space_directory: &str,
+    ) -> Arc<str> {
+        if let Some(add_prefix) = add_prefix {
+            if add_prefix.starts_with("//") {
+                format!("{workspace_directory}/{add_prefix}").into()
+            } else if add_prefix.starts_with('/') {
+                add_prefix.clone()
+            } else {
+                format!("{workspace_directory}/{add_prefix}").into()
+            }
+        } else {
+            format!("{workspace_directory}/{space_directory}").into()
+        }
+    }
+
     pub fn create_hard_link(target_path: String, source: String) -> anyhow::Result<()> {
         let target = std::path::Path::new(target_path.as_str());
         let original = std::path::Path::new(source.as_str());
@@ -316,14 +563,23 @@ impl HttpArchive {
             ))?;
 
             #[cfg(windows)]
-            #[cfg(unix)]
-            std::os::windows::fs::symlink_file(link.clone(), target).context(format_context!(
-                "failed to create symlink {link:?} -> {target_path}"
-            ))?;
+            {
+                let link = std::fs::read_link(original)
+                    .context(format_context!("failed to read symlink {original:?}"))?;
+                if original.is_dir() {
+                    std::os::windows::fs::symlink_dir(link.clone(), target)
+                } else {
+                    std::os::windows::fs::symlink_file(link.clone(), target)
+                }
+                .context(format_context!(
+                    "failed to create symlink {link:?} -> {target_path}"
+                ))?;
+            }
 
             return Ok(());
         }
 
+        // the hard link shares the inode so the extracted file's mode is kept
         std::fs::hard_link(original, target).context(format_context!(
             "If you get 'Operation Not Permitted' on mac try enabling 'Full Disk Access' for the terminal",
         ))?;
@@ -335,19 +591,32 @@ impl HttpArchive {
         &self,
         mut progress_bar: printer::MultiProgressBar,
     ) -> anyhow::Result<printer::MultiProgressBar> {
-        let runtime = tokio::runtime::Builder::new_multi_thread()
-            .worker_threads(1)
-            .enable_all()
-            .build()
-            .context(format_context!("Failed to create runtime"))?;
+        if self.is_download_required() && get_is_offline() {
+            return Err(format_error!(
+                "--offline: {} is not in the store at {}",
+                self.archive.url,
+                self.full_path_to_archive
+            ));
+        }
 
         let mut next_progress_bar = if self.is_download_required() {
-            if let Some(arguments) = gh::transform_url_to_arguments(
+            let next_progress_bar = if let Some(arguments) = gh::transform_url_to_arguments(
                 self.allow_gh_for_download,
                 self.archive.url.as_ref(),
                 &self.full_path_to_archive,
             ) {
                 let gh_command = format!("{}/gh", self.tools_path);
+                gh::check_version(&gh_command)
+                    .context(format_context!("Failed to check gh for {}", self.archive.url))?;
+
+                // gh downloads count against the same limit as reqwest downloads
+                progress_bar.set_message("Waiting for a download slot");
+                let _download_slot = get_runtime()?
+                    .block_on(get_download_slots().acquire())
+                    .context(format_context!(
+                        "Failed to acquire a download slot for {}",
+                        self.archive.url
+                    ))?;
                 gh::download(&gh_command, &self.archive.url, arguments, &mut progress_bar)
                     .context(format_context!("Failed to download using gh"))?;
 
@@ -356,11 +625,17 @@ impl HttpArchive {
                 label_logger(&mut progress_bar, &self.archive.url)
                     .debug(format!("{} Downloading using reqwest", self.archive.url).as_str());
 
+                let runtime = get_runtime()?;
                 let join_handle = self
-                    .download(&runtime, progress_bar)
+                    .download(runtime, progress_bar)
                     .context(format_context!("Failed to download using reqwest"))?;
                 runtime.block_on(join_handle)??
-            }
+            };
+            self.verify_download().context(format_context!(
+                "Failed to verify the download of {}",
+                self.archive.url
+            ))?;
+            next_progress_bar
         } else {
             label_logger(&mut progress_bar, &self.archive.url)
                 .debug(format!("{} download not required", self.archive.url).as_str());
@@ -376,6 +651,27 @@ impl HttpArchive {
         Ok(next_progress_bar)
     }
 
+    /// Checks the sha256 of a download that is not extracted (extraction checks archives).
+    /// Each archive is checked on its own so a bad download does not affect the others.
+    fn verify_download(&self) -> anyhow::Result<()> {
+        if self.archive_driver.is_some() {
+            return Ok(());
+        }
+        let path = std::path::Path::new(self.full_path_to_archive.as_str());
+        let digest = sha256::try_digest(path)
+            .context(format_context!("Failed to hash {path:?}"))?;
+        if digest != self.archive.sha256.as_ref() {
+            // remove the file so the next sync downloads it again
+            let _ = std::fs::remove_file(path);
+            return Err(format_error!(
+                "sha256 mismatch for {}: expected {} got {digest}",
+                self.archive.url,
+                self.archive.sha256
+            ));
+        }
+        Ok(())
+    }
+
     pub fn download(
         &self,
         runtime: &tokio::runtime::Runtime,
@@ -490,3 +786,220 @@ impl HttpArchive {
         Ok(relative_path)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A store and a workspace in a temporary directory that is removed on drop
+    struct Fixture {
+        root: std::path::PathBuf,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("http_archive_{name}_{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            Self { root }
+        }
+
+        fn workspace(&self) -> std::path::PathBuf {
+            self.root.join("workspace")
+        }
+
+        fn new_http_archive(&self, spaces_key: &str, archive: Archive) -> HttpArchive {
+            HttpArchive::new(
+                self.root.join("store").to_string_lossy().as_ref(),
+                spaces_key,
+                &archive,
+                self.root.to_string_lossy().as_ref(),
+            )
+            .unwrap()
+        }
+
+        /// Stands in for the download, `extract()` moves it to the extracted files
+        fn write_download(&self, http_archive: &HttpArchive, content: &str) -> std::path::PathBuf {
+            let downloaded = std::path::PathBuf::from(http_archive.full_path_to_archive.as_str());
+            std::fs::create_dir_all(downloaded.parent().unwrap()).unwrap();
+            std::fs::write(&downloaded, content).unwrap();
+            downloaded
+        }
+
+        fn create_links(&self, http_archive: &HttpArchive, spaces_key: &str, is_extract: bool) {
+            let mut printer = printer::Printer::new_stdout();
+            let mut multi_progress = printer::MultiProgress::new(&mut printer);
+            let mut progress = multi_progress.add_progress("test", None, None);
+            if is_extract {
+                progress = http_archive.extract(progress).unwrap();
+            }
+            http_archive
+                .create_links(
+                    progress,
+                    self.workspace().to_string_lossy().as_ref(),
+                    spaces_key,
+                )
+                .unwrap();
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn new_archive(url: &str) -> Archive {
+        Archive {
+            url: url.into(),
+            sha256: "0".repeat(64).into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rate_limiter_waits_for_the_bucket_to_refill() {
+        let start = std::time::Instant::now();
+        let mut rate_limiter = RateLimiter::new(1000, start);
+
+        // a one second burst is allowed
+        assert!(rate_limiter.consume(1000, start).is_zero());
+        // the next 500 bytes need half a second
+        let delay = rate_limiter.consume(500, start);
+        assert_eq!(delay.as_millis(), 500);
+        // after waiting the debt is paid off
+        let later = start + std::time::Duration::from_secs(1);
+        assert!(rate_limiter.consume(100, later).is_zero());
+    }
+
+    #[test]
+    fn download_only_path_includes_sha256() {
+        let fixture = Fixture::new("sha");
+        let get_path = |sha256: &str| {
+            let archive = Archive {
+                sha256: sha256.into(),
+                extract: Some(false),
+                ..new_archive("https://example.com/files/firmware.tar.gz")
+            };
+            let http_archive = fixture.new_http_archive("firmware", archive);
+            assert!(http_archive.archive_driver.is_none());
+            http_archive.full_path_to_archive
+        };
+
+        let first = get_path(&"a".repeat(64));
+        let second = get_path(&"b".repeat(64));
+        assert_ne!(first, second);
+        assert!(first.ends_with(&format!("{}/firmware.tar.gz", "a".repeat(64))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn create_links_keeps_executable_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let fixture = Fixture::new("mode");
+        let http_archive =
+            fixture.new_http_archive("tools", new_archive("https://example.com/files/run.sh"));
+        let downloaded = fixture.write_download(&http_archive, "#!/bin/sh\necho hello\n");
+        std::fs::set_permissions(&downloaded, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        fixture.create_links(&http_archive, "tools", true);
+
+        let script = fixture.workspace().join("tools").join("run.sh");
+        let mode = std::fs::metadata(&script).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn create_links_symlinks_extracted_directory() {
+        let fixture = Fixture::new("link");
+        let archive = Archive {
+            link: ArchiveLink::Symlink,
+            ..new_archive("https://example.com/files/run.sh")
+        };
+        let http_archive = fixture.new_http_archive("tools", archive);
+        fixture.write_download(&http_archive, "#!/bin/sh\necho hello\n");
+
+        fixture.create_links(&http_archive, "tools", true);
+
+        let tools = fixture.workspace().join("tools");
+        assert!(tools.is_symlink());
+        assert!(tools.join("run.sh").is_file());
+    }
+
+    #[test]
+    fn remap_first_match_wins_and_falls_back_to_prefixes() {
+        let fixture = Fixture::new("remap");
+        let remap = |glob: &str, strip_prefix: Option<&str>, add_prefix: &str| ArchiveRemap {
+            glob: glob.into(),
+            strip_prefix: strip_prefix.map(|prefix| prefix.into()),
+            add_prefix: Some(add_prefix.into()),
+        };
+        let archive = Archive {
+            strip_prefix: Some("pkg/".into()),
+            add_prefix: Some("sysroot".into()),
+            remap: Some(vec![
+                remap("pkg/bin/*", Some("pkg/bin/"), "sysroot/bin"),
+                remap("pkg/bin/tool", None, "other"),
+            ]),
+            ..new_archive("https://example.com/files/pkg.tar.gz")
+        };
+        let http_archive = fixture.new_http_archive("pkg", archive);
+
+        let files = ["pkg/bin/tool", "pkg/share/doc.txt", "README"];
+        let extracted = std::path::PathBuf::from(http_archive.get_path_to_extracted_files());
+        for file in files {
+            let path = extracted.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, file).unwrap();
+        }
+        http_archive
+            .save_files_json(Files {
+                files: files.into_iter().map(|file| file.into()).collect(),
+            })
+            .unwrap();
+
+        fixture.create_links(&http_archive, "pkg", false);
+
+        let workspace = fixture.workspace();
+        assert!(workspace.join("sysroot/bin/tool").is_file());
+        assert!(!workspace.join("other").exists());
+        // unmatched files use the archive strip_prefix and add_prefix
+        assert!(workspace.join("sysroot/share/doc.txt").is_file());
+        // files outside of strip_prefix are not linked
+        assert!(!workspace.join("sysroot/README").exists());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn create_links_preserves_symlinks() {
+        let fixture = Fixture::new("symlink");
+        let http_archive =
+            fixture.new_http_archive("data", new_archive("https://example.com/files/data"));
+
+        let extracted = std::path::PathBuf::from(http_archive.get_path_to_extracted_files());
+        std::fs::create_dir_all(&extracted).unwrap();
+        std::fs::write(extracted.join("file.txt"), "content").unwrap();
+        // creating symlinks on windows requires developer mode or admin rights
+        if std::os::windows::fs::symlink_file("file.txt", extracted.join("link.txt")).is_err() {
+            return;
+        }
+
+        http_archive
+            .save_files_json(Files {
+                files: ["file.txt".into(), "link.txt".into()].into_iter().collect(),
+            })
+            .unwrap();
+
+        fixture.create_links(&http_archive, "data", false);
+
+        let link = fixture.workspace().join("data").join("link.txt");
+        assert!(link.is_symlink());
+        assert_eq!(
+            std::fs::read_link(&link).unwrap(),
+            std::path::PathBuf::from("file.txt")
+        );
+        assert_eq!(std::fs::read_to_string(&link).unwrap(), "content");
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+/// Set to `1` or `true` to read credentials for archive downloads from `.netrc`
+pub const USE_NETRC_ENV_VAR: &str = "SPACES_USE_NETRC";
+
+pub struct Credentials {
+    pub login: Arc<str>,
+    pub password: Arc<str>,
+}
+
+fn is_enabled() -> bool {
+    std::env::var(USE_NETRC_ENV_VAR)
+        .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+fn get_netrc_path() -> Option<std::path::PathBuf> {
+    if let Ok(path) = std::env::var("NETRC") {
+        return Some(path.into());
+    }
+    let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).ok()?;
+    Some(std::path::Path::new(&home).join(".netrc"))
+}
+
+/// Returns the credentials for the host of `url` if `.netrc` is enabled and has an entry.
+/// The credentials are only kept in memory, they are never written to the store.
+pub fn get_credentials(url: &str) -> Option<Credentials> {
+    if !is_enabled() {
+        return None;
+    }
+    let host = url::Url::parse(url).ok()?.host_str()?.to_string();
+    let content = std::fs::read_to_string(get_netrc_path()?).ok()?;
+    parse(content.as_str(), host.as_str())
+}
+
+/// Finds the `machine` entry for `host` (or the `default` entry) in `.netrc` content.
+fn parse(content: &str, host: &str) -> Option<Credentials> {
+    let mut tokens = content.split_whitespace();
+    let mut is_match = false;
+    let mut is_found = false;
+    let mut login = None;
+    let mut password = None;
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" | "default" => {
+                if is_match {
+                    break;
+                }
+                is_match = token == "default" || tokens.next() == Some(host);
+                is_found |= is_match;
+            }
+            "login" => {
+                let value = tokens.next();
+                if is_match {
+                    login = value;
+                }
+            }
+            "password" => {
+                let value = tokens.next();
+                if is_match {
+                    password = value;
+                }
+            }
+            "account" => {
+                tokens.next();
+            }
+            _ => {}
+        }
+    }
+
+    if !is_found {
+        return None;
+    }
+
+    Some(Credentials {
+        login: login.unwrap_or_default().into(),
+        password: password?.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_matches_machine_or_default() {
+        let content = "machine other.com login a password b\n\
+            machine example.com\n  login user\n  password secret\n\
+            default login anonymous password guest\n";
+
+        let credentials = parse(content, "example.com").unwrap();
+        assert_eq!(credentials.login.as_ref(), "user");
+        assert_eq!(credentials.password.as_ref(), "secret");
+
+        let credentials = parse(content, "unknown.com").unwrap();
+        assert_eq!(credentials.login.as_ref(), "anonymous");
+
+        assert!(parse("machine other.com login a password b", "example.com").is_none());
+    }
+}
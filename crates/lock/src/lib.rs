@@ -16,6 +16,9 @@ impl<ModuleState: std::fmt::Debug> StateLock<ModuleState> {
     }
 
     pub fn read(&self) -> RwLockReadGuard<ModuleState> {
+        #[cfg(feature = "instrumentation")]
+        let _timer = instrumentation::Timer::new::<ModuleState>("read");
+
         self.lock.read().unwrap_or_else(|_| {
             panic!(
                 "Internal error: failed to get read lock for {:?}",
@@ -25,6 +28,9 @@ impl<ModuleState: std::fmt::Debug> StateLock<ModuleState> {
     }
 
     pub fn write(&self) -> RwLockWriteGuard<ModuleState> {
+        #[cfg(feature = "instrumentation")]
+        let _timer = instrumentation::Timer::new::<ModuleState>("write");
+
         self.lock.write().unwrap_or_else(|_| {
             panic!(
                 "Internal error: failed to get write lock for {:?}",
@@ -34,6 +40,77 @@ impl<ModuleState: std::fmt::Debug> StateLock<ModuleState> {
     }
 }
 
+#[cfg(feature = "instrumentation")]
+mod instrumentation {
+    use std::sync::Mutex;
+
+    const SLOW_ACQUISITION_THRESHOLD: std::time::Duration = std::time::Duration::from_millis(10);
+
+    pub(crate) struct SlowAcquisition {
+        pub(crate) state: &'static str,
+        pub(crate) access: &'static str,
+        pub(crate) elapsed: std::time::Duration,
+    }
+
+    pub(crate) static SLOW_ACQUISITIONS: Mutex<Vec<SlowAcquisition>> = Mutex::new(Vec::new());
+
+    // measures from creation until drop, which is when the lock has been acquired
+    pub(crate) struct Timer {
+        state: &'static str,
+        access: &'static str,
+        start: std::time::Instant,
+    }
+
+    impl Timer {
+        pub(crate) fn new<ModuleState>(access: &'static str) -> Self {
+            Self {
+                state: std::any::type_name::<ModuleState>(),
+                access,
+                start: std::time::Instant::now(),
+            }
+        }
+    }
+
+    impl Drop for Timer {
+        fn drop(&mut self) {
+            let elapsed = self.start.elapsed();
+            if elapsed > SLOW_ACQUISITION_THRESHOLD {
+                if let Ok(mut slow_acquisitions) = SLOW_ACQUISITIONS.lock() {
+                    slow_acquisitions.push(SlowAcquisition {
+                        state: self.state,
+                        access: self.access,
+                        elapsed,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Logs StateLock acquisitions that exceeded the threshold. This is a no-op
+/// unless the `instrumentation` feature is enabled.
+pub fn report_slow_acquisitions(printer: &mut printer::Printer) {
+    #[cfg(feature = "instrumentation")]
+    {
+        let slow_acquisitions = instrumentation::SLOW_ACQUISITIONS
+            .lock()
+            .map(|mut slow_acquisitions| std::mem::take(&mut *slow_acquisitions))
+            .unwrap_or_default();
+        for slow_acquisition in slow_acquisitions {
+            logger::Logger::new_printer(printer, "lock".into()).warning(
+                format!(
+                    "{} {} lock took {:?}",
+                    slow_acquisition.state, slow_acquisition.access, slow_acquisition.elapsed
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    #[cfg(not(feature = "instrumentation"))]
+    let _ = printer;
+}
+
 pub fn get_process_group_id_env_name() -> &'static str {
     const SPACES_PROCESS_GROUP_ENV_VAR: &str = "SPACES_PROCESS_GROUP_ID";
     SPACES_PROCESS_GROUP_ENV_VAR
@@ -14,10 +14,54 @@ pub enum Platform {
     LinuxX86_64,
     #[serde(rename = "linux-aarch64")]
     LinuxAarch64,
+    #[serde(rename = "linux-riscv64")]
+    LinuxRiscv64,
+    #[serde(rename = "freebsd-x86_64")]
+    FreebsdX86_64,
 }
 
+const SPACES_PLATFORM_ENV_VAR: &str = "SPACES_PLATFORM";
+
 impl Platform {
+    pub fn from_name(name: &str) -> Option<Platform> {
+        Self::get_supported_platforms()
+            .into_iter()
+            .find(|platform| platform.to_string() == name)
+    }
+
+    fn parse_platform_override(name: &str) -> Result<Platform, String> {
+        Self::from_name(name.trim()).ok_or_else(|| {
+            let names: Vec<String> = Self::get_supported_platforms()
+                .iter()
+                .map(|platform| platform.to_string())
+                .collect();
+            format!(
+                "{SPACES_PLATFORM_ENV_VAR}={name} is not a supported platform, use one of: {}",
+                names.join(", ")
+            )
+        })
+    }
+
+    /// Returns the platform named by `SPACES_PLATFORM` or `None` if it is not set. Fails
+    /// with the supported platform names if the value is not one of them.
+    pub fn get_platform_override() -> Result<Option<Platform>, String> {
+        match std::env::var(SPACES_PLATFORM_ENV_VAR) {
+            Ok(name) => Self::parse_platform_override(name.as_str()).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Returns the platform named by `SPACES_PLATFORM` if it is set, otherwise
+    /// the platform spaces was compiled for. Use `get_platform_override()` to
+    /// check for an invalid `SPACES_PLATFORM`.
     pub fn get_platform() -> Option<Platform> {
+        if let Ok(name) = std::env::var(SPACES_PLATFORM_ENV_VAR) {
+            return Self::from_name(name.trim());
+        }
+        Self::get_host_platform()
+    }
+
+    pub fn get_host_platform() -> Option<Platform> {
         if cfg!(target_os = "macos") {
             if cfg!(target_arch = "x86_64") {
                 return Some(Self::MacosX86_64);
@@ -35,6 +79,12 @@ impl Platform {
                 return Some(Self::LinuxX86_64);
             } else if cfg!(target_arch = "aarch64") {
                 return Some(Self::LinuxAarch64);
+            } else if cfg!(target_arch = "riscv64") {
+                return Some(Self::LinuxRiscv64);
+            }
+        } else if cfg!(target_os = "freebsd") {
+            if cfg!(target_arch = "x86_64") {
+                return Some(Self::FreebsdX86_64);
             }
         }
         None
@@ -48,6 +98,8 @@ impl Platform {
             Self::WindowsAarch64,
             Self::LinuxX86_64,
             Self::LinuxAarch64,
+            Self::LinuxRiscv64,
+            Self::FreebsdX86_64,
         ]
     }
 
@@ -68,14 +120,21 @@ impl Platform {
     pub fn is_linux() -> bool {
         matches!(
             Self::get_platform(),
-            Some(Self::LinuxAarch64) | Some(Self::LinuxX86_64)
+            Some(Self::LinuxAarch64) | Some(Self::LinuxX86_64) | Some(Self::LinuxRiscv64)
         )
     }
 
+    pub fn is_freebsd() -> bool {
+        matches!(Self::get_platform(), Some(Self::FreebsdX86_64))
+    }
+
     pub fn is_x86_64() -> bool {
         matches!(
             Self::get_platform(),
-            Some(Self::WindowsX86_64) | Some(Self::LinuxX86_64) | Some(Self::MacosX86_64)
+            Some(Self::WindowsX86_64)
+                | Some(Self::LinuxX86_64)
+                | Some(Self::MacosX86_64)
+                | Some(Self::FreebsdX86_64)
         )
     }
 
@@ -85,6 +144,10 @@ impl Platform {
             Some(Self::MacosAarch64) | Some(Self::WindowsAarch64) | Some(Self::LinuxAarch64)
         )
     }
+
+    pub fn is_riscv64() -> bool {
+        matches!(Self::get_platform(), Some(Self::LinuxRiscv64))
+    }
 }
 
 impl std::fmt::Display for Platform {
@@ -96,6 +159,31 @@ impl std::fmt::Display for Platform {
             Platform::WindowsAarch64 => write!(f, "windows-aarch64"),
             Platform::LinuxX86_64 => write!(f, "linux-x86_64"),
             Platform::LinuxAarch64 => write!(f, "linux-aarch64"),
+            Platform::LinuxRiscv64 => write!(f, "linux-riscv64"),
+            Platform::FreebsdX86_64 => write!(f, "freebsd-x86_64"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platform_override_names() {
+        assert_eq!(
+            Platform::parse_platform_override("linux-aarch64"),
+            Ok(Platform::LinuxAarch64)
+        );
+        assert_eq!(
+            Platform::parse_platform_override(" macos-x86_64\n"),
+            Ok(Platform::MacosX86_64)
+        );
+
+        let error = Platform::parse_platform_override("linux-arm64").unwrap_err();
+        assert!(error.contains("SPACES_PLATFORM=linux-arm64"));
+        for platform in Platform::get_supported_platforms() {
+            assert!(error.contains(platform.to_string().as_str()));
         }
     }
 }
@@ -1,10 +1,16 @@
-use crate::{docs, evaluator, rules, runner, singleton, tools, workspace};
+use crate::{
+    config, docs, doctor, evaluator, events, rules, runner, singleton, summary, tools, workspace,
+};
 use anyhow::Context;
 use anyhow_source_location::{format_context, format_error};
-use clap::{CommandFactory, Parser, Subcommand, ValueEnum, ValueHint};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum, ValueHint};
+use serde::Deserialize;
 use std::sync::Arc;
 
-#[derive(ValueEnum, Clone, Copy, Debug)]
+const DEFAULT_SUMMARY_OUTPUT: &str = "spaces-summary.xml";
+
+#[derive(ValueEnum, Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Level {
     Trace,
     Debug,
@@ -12,6 +18,16 @@ pub enum Level {
     Info,
     Warning,
     Error,
+    /// Same as `--verbosity=warning --log-format=json`
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Default)]
+enum LogFormat {
+    #[default]
+    Text,
+    /// Each log line is a JSON object with `level`, `label`, `message` and `timestamp`
+    Json,
 }
 
 impl From<Level> for printer::Level {
@@ -23,6 +39,27 @@ impl From<Level> for printer::Level {
             Level::Info => printer::Level::Info,
             Level::Warning => printer::Level::Warning,
             Level::Error => printer::Level::Error,
+            Level::Json => printer::Level::Warning,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum DirtyPolicy {
+    /// Run the checkout as is, git aborts if the local changes conflict
+    Fail,
+    /// Stash the local changes before the checkout and restore them after
+    Stash,
+    /// Leave the repository untouched
+    Skip,
+}
+
+impl From<DirtyPolicy> for git::DirtyPolicy {
+    fn from(policy: DirtyPolicy) -> Self {
+        match policy {
+            DirtyPolicy::Fail => git::DirtyPolicy::Fail,
+            DirtyPolicy::Stash => git::DirtyPolicy::Stash,
+            DirtyPolicy::Skip => git::DirtyPolicy::Skip,
         }
     }
 }
@@ -33,33 +70,81 @@ pub struct Arguments {
     /// The verbosity level of the output.
     #[arg(short, long, default_value = "warning")]
     pub verbosity: Level,
-    #[arg(long)]
+    /// The format of the log lines, use with `--verbosity` to choose the level.
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    #[arg(long, overrides_with = "no_hide_progress_bars")]
     /// Dont show progress bars
     pub hide_progress_bars: bool,
+    /// Show progress bars even if `.spaces/config.toml` sets hide_progress_bars.
+    #[arg(long, overrides_with = "hide_progress_bars")]
+    no_hide_progress_bars: bool,
     /// If this is passed, info.is_ci() returns true in scripts.
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_ci")]
     ci: bool,
+    /// Turn off CI mode even if `.spaces/config.toml` sets ci.
+    #[arg(long, overrides_with = "ci")]
+    no_ci: bool,
+    /// Turn warnings about the workspace (e.g. it was created by a newer spaces) into errors.
+    #[arg(long)]
+    strict: bool,
+    /// Fail instead of accessing the network. Repositories and archives must already be in the store (exec rules are not restricted).
+    #[arg(long)]
+    offline: bool,
+    /// Limit the combined rate of archive downloads in bytes per second (default is unlimited). Downloads using gh are not limited.
+    #[arg(long, value_name = "BYTES_PER_SEC", value_parser = clap::value_parser!(u64).range(1..))]
+    download_rate_limit: Option<u64>,
     #[command(subcommand)]
     commands: Commands,
 }
 
+fn read_targets_from_stdin() -> anyhow::Result<Vec<Arc<str>>> {
+    use std::io::BufRead;
+    let mut targets = Vec::new();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.context(format_context!("Failed to read target from stdin"))?;
+        let target = line.trim();
+        if !target.is_empty() {
+            targets.push(target.into());
+        }
+    }
+    if targets.is_empty() {
+        return Err(format_error!("No targets were read from stdin"));
+    }
+    Ok(targets)
+}
+
+/// `--<flag>` and `--no-<flag>` as a CLI value, `None` lets the config decide
+fn get_cli_flag(is_set: bool, is_unset: bool) -> Option<bool> {
+    match (is_set, is_unset) {
+        (true, _) => Some(true),
+        (false, true) => Some(false),
+        (false, false) => None,
+    }
+}
+
 fn handle_verbosity(
     printer: &mut printer::Printer,
-    verbosity: printer::Level,
+    verbosity: Level,
     is_ci: bool,
     is_hide_progress_bars: bool,
 ) {
+    let is_json = logger::get_is_json();
     if is_ci {
         singleton::set_ci(true);
         printer.verbosity.level = printer::Level::Trace;
         printer.verbosity.is_show_progress_bars = false;
     } else {
-        printer.verbosity.level = verbosity;
-        printer.verbosity.is_show_progress_bars = !is_hide_progress_bars;
+        printer.verbosity.level = verbosity.into();
+        // progress bars would be interleaved with the JSON lines
+        printer.verbosity.is_show_progress_bars = !is_hide_progress_bars && !is_json;
     }
 }
 
 pub fn execute() -> anyhow::Result<()> {
+    // a misspelled SPACES_PLATFORM would otherwise look like an unsupported platform
+    platform::Platform::get_platform_override().map_err(|error| format_error!("{error}"))?;
+
     if std::env::args().len() == 1 {
         let mut stdin_contents = String::new();
         use std::io::Read;
@@ -89,7 +174,43 @@ pub fn execute() -> anyhow::Result<()> {
         }
     }
 
-    let args = Arguments::parse();
+    let matches = Arguments::command().get_matches();
+    let mut args = Arguments::from_arg_matches(&matches)
+        .context(format_context!("Failed to parse arguments"))?;
+
+    // precedence: CLI flag > workspace config > env > user config > built-in default
+    let config = config::Config::load().context(format_context!("Failed to load config"))?;
+    let is_verbosity_explicit =
+        matches.value_source("verbosity") == Some(clap::parser::ValueSource::CommandLine);
+    args.verbosity = config::resolve(
+        is_verbosity_explicit.then_some(args.verbosity),
+        config.verbosity,
+        args.verbosity,
+    );
+    args.hide_progress_bars = config::resolve(
+        get_cli_flag(args.hide_progress_bars, args.no_hide_progress_bars),
+        config.hide_progress_bars,
+        false,
+    );
+    args.ci = config::resolve(get_cli_flag(args.ci, args.no_ci), config.ci, false);
+    if let Some(jobs) = config.jobs {
+        singleton::set_max_queue_count(jobs);
+    }
+    singleton::set_store_path(config.store_path);
+    singleton::set_allow_gh(config.allow_gh.unwrap_or(true));
+    if let Some(max_concurrent_downloads) = config.max_concurrent_downloads {
+        http_archive::set_max_concurrent_downloads(max_concurrent_downloads);
+    }
+    singleton::set_strict(args.strict);
+    singleton::set_offline(args.offline);
+    git::set_is_offline(args.offline);
+    http_archive::set_is_offline(args.offline);
+    http_archive::set_download_rate_limit(args.download_rate_limit);
+
+    logger::set_is_json(
+        args.log_format == LogFormat::Json || matches!(args.verbosity, Level::Json),
+    );
+
     let mut printer = printer::Printer::new_stdout();
 
     match args {
@@ -103,10 +224,21 @@ pub fn execute() -> anyhow::Result<()> {
                     script,
                     workflow,
                     create_lock_file,
+                    frozen,
                     force_install_tools,
+                    consolidate_git_logs,
+                    print_plan,
+                    dirty_policy,
+                    trace_git,
                 },
+            ..
         } => {
-            handle_verbosity(&mut printer, verbosity.into(), ci, hide_progress_bars);
+            handle_verbosity(&mut printer, verbosity, ci, hide_progress_bars);
+            git::set_is_consolidated_log(consolidate_git_logs);
+            git::set_is_trace(trace_git);
+            singleton::set_dirty_policy(dirty_policy.into());
+            singleton::set_print_plan(print_plan);
+            singleton::set_frozen(frozen);
 
             let mut inputs: Vec<Arc<str>> = vec![];
             inputs.extend(script.clone());
@@ -117,13 +249,28 @@ pub fn execute() -> anyhow::Result<()> {
                 }
                 let directory = parts[0];
                 let scripts = parts[1].split(',');
+                let mut missing_scripts = Vec::new();
                 for script in scripts {
-                    inputs.push(format!("{}/{}", directory, script).into());
+                    let script_path: Arc<str> = format!("{}/{}", directory, script).into();
+                    if !runner::is_checkout_script_present(script_path.as_ref()) {
+                        missing_scripts.push(format!("  {script} -> {script_path}"));
+                    }
+                    inputs.push(script_path);
+                }
+
+                // report every missing script before starting the checkout
+                if !missing_scripts.is_empty() {
+                    return Err(format_error!(
+                        "Workflow {workflow} references scripts that do not exist:\n{}",
+                        missing_scripts.join("\n")
+                    ));
                 }
             }
 
-            tools::install_tools(&mut printer, force_install_tools)
-                .context(format_context!("while installing tools"))?;
+            if !print_plan {
+                tools::install_tools(&mut printer, force_install_tools)
+                    .context(format_context!("while installing tools"))?;
+            }
 
             runner::checkout(&mut printer, name, inputs, create_lock_file)
                 .context(format_context!("during runner checkout"))?;
@@ -133,14 +280,25 @@ pub fn execute() -> anyhow::Result<()> {
             verbosity,
             hide_progress_bars,
             ci,
-            commands: Commands::Sync {},
+            commands:
+                Commands::Sync {
+                    consolidate_git_logs,
+                    jobs,
+                    dirty_policy,
+                    trace_git,
+                },
+            ..
         } => {
-            handle_verbosity(&mut printer, verbosity.into(), ci, hide_progress_bars);
+            handle_verbosity(&mut printer, verbosity, ci, hide_progress_bars);
+            singleton::set_jobs(jobs);
+            git::set_is_consolidated_log(consolidate_git_logs);
+            git::set_is_trace(trace_git);
+            singleton::set_dirty_policy(dirty_policy.into());
             runner::run_starlark_modules_in_workspace(
                 &mut printer,
                 rules::Phase::Checkout,
                 None,
-                runner::RunWorkspace::Target(None),
+                runner::RunWorkspace::Target(Vec::new()),
                 false,
             )
             .context(format_context!("during runner sync"))?;
@@ -150,15 +308,80 @@ pub fn execute() -> anyhow::Result<()> {
             verbosity,
             hide_progress_bars,
             ci,
-            commands: Commands::Run { target },
+            commands:
+                Commands::Run {
+                    target,
+                    targets_from_stdin,
+                    include_optional,
+                    until,
+                    export_artifacts,
+                    repeat,
+                    isolate_tmp,
+                    keep_tmp_on_failure,
+                    summary_format,
+                    summary_output,
+                    report_slow,
+                    dry_run,
+                    json_events,
+                    jobs,
+                },
+            ..
         } => {
-            handle_verbosity(&mut printer, verbosity.into(), ci, hide_progress_bars);
+            handle_verbosity(&mut printer, verbosity, ci, hide_progress_bars);
+            singleton::set_jobs(jobs);
+            singleton::set_dry_run(dry_run);
+            singleton::set_include_optional(include_optional);
+            singleton::set_run_until(until);
+            singleton::set_isolate_tmp(isolate_tmp);
+            singleton::set_keep_tmp_on_failure(keep_tmp_on_failure);
+            singleton::set_report_slow(report_slow.map(|count| count as usize));
+            if let Some(repeat) = repeat {
+                singleton::set_repeat(repeat);
+                if repeat > 1 {
+                    // every repetition executes the rules even if the inputs are the same
+                    singleton::set_ignore_inputs(true);
+                }
+            }
+
+            if let Some(json_events) = json_events {
+                // open the file before the workspace changes the working directory
+                events::set_output(json_events.as_ref())
+                    .context(format_context!("while setting up json events"))?;
+            }
+
+            if let Some(export_artifacts) = export_artifacts {
+                // resolve the export path before the workspace changes the working directory
+                let current_working_directory = std::env::current_dir()
+                    .context(format_context!("Failed to get current working directory"))?;
+                let directory = current_working_directory
+                    .join(export_artifacts.as_ref())
+                    .to_string_lossy()
+                    .into();
+                singleton::set_export_artifacts(Some(directory));
+            }
+
+            if let Some(format) = summary_format {
+                // resolve the output path before the workspace changes the working directory
+                let current_working_directory = std::env::current_dir()
+                    .context(format_context!("Failed to get current working directory"))?;
+                let output = current_working_directory
+                    .join(summary_output.as_deref().unwrap_or(DEFAULT_SUMMARY_OUTPUT))
+                    .to_string_lossy()
+                    .into();
+                singleton::set_summary(Some(summary::Summary { format, output }));
+            }
+
+            let targets = if targets_from_stdin {
+                read_targets_from_stdin().context(format_context!("while reading targets"))?
+            } else {
+                target
+            };
 
             runner::run_starlark_modules_in_workspace(
                 &mut printer,
                 rules::Phase::Run,
                 None,
-                runner::RunWorkspace::Target(target),
+                runner::RunWorkspace::Target(targets),
                 false,
             )
             .context(format_context!("while executing run rules"))?;
@@ -168,9 +391,45 @@ pub fn execute() -> anyhow::Result<()> {
             verbosity,
             hide_progress_bars,
             ci,
-            commands: Commands::Evaluate { target },
+            commands: Commands::Clean { target },
+            ..
         } => {
-            handle_verbosity(&mut printer, verbosity.into(), ci, hide_progress_bars);
+            handle_verbosity(&mut printer, verbosity, ci, hide_progress_bars);
+
+            runner::run_starlark_modules_in_workspace(
+                &mut printer,
+                rules::Phase::Clean,
+                None,
+                runner::RunWorkspace::Target(target.into_iter().collect()),
+                false,
+            )
+            .context(format_context!("while cleaning run rules"))?;
+        }
+
+        Arguments {
+            verbosity,
+            hide_progress_bars,
+            ci,
+            commands:
+                Commands::Evaluate {
+                    target,
+                    show_inputs,
+                    show_outputs,
+                    format,
+                    graph,
+                },
+            ..
+        } => {
+            handle_verbosity(&mut printer, verbosity, ci, hide_progress_bars);
+            singleton::set_show_graph(graph);
+            singleton::set_list_format(format.unwrap_or(rules::ListFormat::Yaml));
+            if show_inputs || show_outputs {
+                singleton::set_show_files(Some(rules::ShowFiles {
+                    is_inputs: show_inputs,
+                    is_outputs: show_outputs,
+                    format: format.unwrap_or(rules::ListFormat::Yaml),
+                }));
+            }
 
             if printer.verbosity.level > printer::Level::Info {
                 printer.verbosity.level = printer::Level::Info;
@@ -180,7 +439,7 @@ pub fn execute() -> anyhow::Result<()> {
                 &mut printer,
                 rules::Phase::Evaluate,
                 None,
-                runner::RunWorkspace::Target(target),
+                runner::RunWorkspace::Target(target.into_iter().collect()),
                 false,
             )
             .context(format_context!("while executing run rules"))?;
@@ -191,8 +450,9 @@ pub fn execute() -> anyhow::Result<()> {
             hide_progress_bars,
             ci,
             commands: Commands::Completions { shell },
+            ..
         } => {
-            handle_verbosity(&mut printer, verbosity.into(), ci, hide_progress_bars);
+            handle_verbosity(&mut printer, verbosity, ci, hide_progress_bars);
 
             clap_complete::generate(
                 shell,
@@ -207,11 +467,50 @@ pub fn execute() -> anyhow::Result<()> {
             hide_progress_bars,
             ci,
             commands: Commands::Docs { item },
+            ..
         } => {
-            handle_verbosity(&mut printer, verbosity.into(), ci, hide_progress_bars);
+            handle_verbosity(&mut printer, verbosity, ci, hide_progress_bars);
 
             docs::show(&mut printer, item)?;
         }
+
+        Arguments {
+            verbosity,
+            hide_progress_bars,
+            ci,
+            commands: Commands::Unshallow { repo },
+            ..
+        } => {
+            handle_verbosity(&mut printer, verbosity, ci, hide_progress_bars);
+
+            runner::unshallow(&mut printer, repo)
+                .context(format_context!("while unshallowing repository"))?;
+        }
+
+        Arguments {
+            verbosity,
+            hide_progress_bars,
+            ci,
+            commands: Commands::Doctor {},
+            ..
+        } => {
+            handle_verbosity(&mut printer, verbosity, ci, hide_progress_bars);
+
+            doctor::run(&mut printer).context(format_context!("while checking the environment"))?;
+        }
+
+        Arguments {
+            verbosity,
+            hide_progress_bars,
+            ci,
+            commands: Commands::Metrics {},
+            ..
+        } => {
+            handle_verbosity(&mut printer, verbosity, ci, hide_progress_bars);
+
+            runner::show_metrics(&mut printer)
+                .context(format_context!("while showing the workspace metrics"))?;
+        }
     }
 
     Ok(())
@@ -233,15 +532,88 @@ enum Commands {
         /// Create a lock file for the workspace. This file can be passed on the next checkout as a script to re-create the exact workspace.
         #[arg(long)]
         create_lock_file: bool,
+        /// Fail if the revisions the git rules resolve to differ from the lock file passed with --script (every difference is reported). Repos are checked against the remote before they are cloned, nothing is cloned once a difference is found. Repos missing from the lock are differences.
+        #[arg(long, conflicts_with = "create_lock_file")]
+        frozen: bool,
         /// Force install the tools spaces needs to run.
         #[arg(long)]
         force_install_tools: bool,
+        /// Append the output of all git commands for a repository to a single log file. Each entry is labeled with the rule, url and sequence number of the command.
+        #[arg(long)]
+        consolidate_git_logs: bool,
+        /// Evaluate the scripts and print the checkout rules without cloning or downloading anything. Scripts inside checked out repos are not evaluated, a script that loads from one fails the plan.
+        #[arg(long)]
+        print_plan: bool,
+        /// What to do with repositories that have local changes. Repos can override this with `dirty_policy`.
+        #[arg(long, value_enum, default_value_t = DirtyPolicy::Fail)]
+        dirty_policy: DirtyPolicy,
+        /// Log every git command line at info level (credentials in urls are redacted).
+        #[arg(long)]
+        trace_git: bool,
     },
     /// Synchronizes the workspace with the checkout rules.
-    Sync {},
+    Sync {
+        /// Append the output of all git commands for a repository to a single log file. Each entry is labeled with the rule, url and sequence number of the command.
+        #[arg(long)]
+        consolidate_git_logs: bool,
+        /// The maximum number of rules to execute at one time (default is the number of logical CPUs or `jobs` in the config). Use -j1 to run rules one at a time, which keeps output from interleaving when debugging.
+        #[arg(short, long, value_parser = clap::value_parser!(i64).range(1..))]
+        jobs: Option<i64>,
+        /// What to do with repositories that have local changes. Repos can override this with `dirty_policy`.
+        #[arg(long, value_enum, default_value_t = DirtyPolicy::Fail)]
+        dirty_policy: DirtyPolicy,
+        /// Log every git command line at info level (credentials in urls are redacted).
+        #[arg(long)]
+        trace_git: bool,
+    },
     /// Executes the Run phase rules.
     Run {
-        /// The name of the target to run (default is all targets).
+        /// The names of the targets to run with their dependencies (default is all targets). Accepts several targets (e.g. --target a b c).
+        #[arg(long, num_args = 1..)]
+        target: Vec<Arc<str>>,
+        /// Read newline-separated target names from stdin and run them with their dependencies.
+        #[arg(long, conflicts_with = "target")]
+        targets_from_stdin: bool,
+        /// Run optional rules along with all other rules. Passing an optional rule to --target only enables the optional rules it depends on.
+        #[arg(long, conflicts_with_all = ["target", "targets_from_stdin"])]
+        include_optional: bool,
+        /// Run the rules ordered before this rule and the rule itself, skipping the rules ordered after it.
+        #[arg(long)]
+        until: Option<Arc<str>>,
+        /// After a successful run, copy the artifacts (default is the declared outputs) of the rules into this directory (namespaced by rule) with a manifest.json.
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        export_artifacts: Option<Arc<str>>,
+        /// Execute the rules this many times without skipping rules with the same inputs and report the pass/fail count and durations (e.g. to find flaky tests).
+        #[arg(long, value_parser = clap::value_parser!(i64).range(1..), conflicts_with = "dry_run")]
+        repeat: Option<i64>,
+        /// Give each exec rule its own temporary directory that is removed when the rule completes (see --keep-tmp-on-failure). TMPDIR (macOS/Linux) and TMP/TEMP (Windows) point to the directory unless the rule sets them.
+        #[arg(long)]
+        isolate_tmp: bool,
+        /// Keep the temporary directory of a rule that fails and log its path.
+        #[arg(long, requires = "isolate_tmp")]
+        keep_tmp_on_failure: bool,
+        /// Write a summary of the rule results in the given format (e.g. for CI test reporting).
+        #[arg(long, value_enum)]
+        summary_format: Option<summary::SummaryFormat>,
+        /// Path to the summary file (default is spaces-summary.xml in the current directory).
+        #[arg(long, requires = "summary_format")]
+        summary_output: Option<Arc<str>>,
+        /// After the run, show the N slowest rules that executed (skipped rules are not ranked).
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(i64).range(1..))]
+        report_slow: Option<i64>,
+        /// Show which rules would execute or be skipped without running anything.
+        #[arg(long)]
+        dry_run: bool,
+        /// Write task events as newline-delimited JSON to the given file (stderr if no file is given).
+        #[arg(long, num_args = 0..=1, default_missing_value = "-")]
+        json_events: Option<Arc<str>>,
+        /// The maximum number of rules to execute at one time (default is the number of logical CPUs or `jobs` in the config). Use -j1 to run rules one at a time, which keeps output from interleaving when debugging.
+        #[arg(short, long, value_parser = clap::value_parser!(i64).range(1..))]
+        jobs: Option<i64>,
+    },
+    /// Removes the build directories and declared outputs of the Run phase rules so they execute on the next run.
+    Clean {
+        /// The name of the target to clean including its dependencies (default is all targets).
         #[arg(long)]
         target: Option<Arc<str>>,
     },
@@ -250,6 +622,18 @@ enum Commands {
         /// The name of the target to evaluate (default is all targets).
         #[arg(long)]
         target: Option<Arc<str>>,
+        /// List the files that contribute to the inputs digest of the target.
+        #[arg(long, requires = "target")]
+        show_inputs: bool,
+        /// List the files on disk that match the declared outputs of the target and flag outputs that are missing.
+        #[arg(long, requires = "target")]
+        show_outputs: bool,
+        /// Output format for the targets, --show-inputs and --show-outputs (default is yaml). The json targets list has the name, phase, type, deps, platforms, inputs and help of each rule.
+        #[arg(long, value_enum, conflicts_with = "graph")]
+        format: Option<rules::ListFormat>,
+        /// Write the dependency graph of the target (default is all targets) as Graphviz DOT or JSON.
+        #[arg(long, value_enum, conflicts_with_all = ["show_inputs", "show_outputs"])]
+        graph: Option<rules::GraphFormat>,
     },
     /// Generates shell completions for the spaces command.
     Completions {
@@ -257,6 +641,16 @@ enum Commands {
         #[arg(long, value_enum)]
         shell: clap_complete::Shell,
     },
+    /// Fetches the full history of a repository that was checked out with a shallow clone.
+    Unshallow {
+        /// The path of the repository relative to the workspace root (same as the checkout rule name)
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        repo: Arc<str>,
+    },
+    /// Checks that the tools, store and workspace settings spaces needs are usable.
+    Doctor {},
+    /// Shows the rule durations of the last checkout or run (stored in .spaces/metrics.spaces.json).
+    Metrics {},
     /// Shows the documentation for spaces starlark modules.
     Docs {
         /// What documentation do you want to see?
@@ -264,3 +658,38 @@ enum Commands {
         item: Option<docs::DocItem>,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_flags_override_the_config() {
+        let parse = |arguments: &[&str]| {
+            let args = Arguments::try_parse_from(["spaces"].iter().chain(arguments).chain(&[
+                "completions",
+                "--shell",
+                "bash",
+            ]))
+            .unwrap();
+            (
+                get_cli_flag(args.hide_progress_bars, args.no_hide_progress_bars),
+                get_cli_flag(args.ci, args.no_ci),
+            )
+        };
+
+        assert_eq!(parse(&[]), (None, None));
+        assert_eq!(
+            parse(&["--hide-progress-bars", "--ci"]),
+            (Some(true), Some(true))
+        );
+        assert_eq!(
+            parse(&["--no-hide-progress-bars", "--no-ci"]),
+            (Some(false), Some(false))
+        );
+        // the last flag wins
+        assert_eq!(parse(&["--ci", "--no-ci"]).1, Some(false));
+        assert_eq!(parse(&["--no-ci", "--ci"]).1, Some(true));
+        assert!(!config::resolve(parse(&["--no-ci"]).1, Some(true), false));
+    }
+}
@@ -22,16 +22,23 @@ pub struct PlatformArchive {
     pub linux_x86_64: Option<http_archive::Archive>,
     #[serde(rename = "linux-aarch64")]
     pub linux_aarch64: Option<http_archive::Archive>,
+    #[serde(rename = "linux-riscv64")]
+    pub linux_riscv64: Option<http_archive::Archive>,
+    #[serde(rename = "freebsd-x86_64")]
+    pub freebsd_x86_64: Option<http_archive::Archive>,
 }
 
 const fn get_archive_dict() -> &'static [(&'static str, &'static str)] {
     &[
-        ("url", "url to zip|tar.xz|tar.gz|tar.bz2 file (can also be an uncompressed file with no suffix)"),
+        ("url", "url to zip|tar.xz|tar.gz|tar.bz2 file (can also be an uncompressed file with no suffix). Set SPACES_USE_NETRC=1 to use basic auth credentials from ~/.netrc (or $NETRC) for the url host"),
         ("sha256", "hash of the file"),
-        ("link", "None|Hard: create hardlinks of the archive from the spaces store to the workspace"),
+        ("link", "None|Hard|Symlink: create hardlinks of the archive from the spaces store to the workspace. Symlink links the whole extracted directory, which is shared by all workspaces and must not be modified (can't be used with `globs` or `remap`)"),
         ("globs", "optional list of globs prefix with `+` to include and `-` to exclude"),
         ("strip_prefix", "optional prefix to strip from the archive path"),
         ("add_prefix", "optional prefix to add in the workspace (e.g. sysroot/share)"),
+        ("remap", "optional list of `{glob, strip_prefix, add_prefix}` applied per file (first match wins, unmatched files use `strip_prefix`/`add_prefix`)"),
+        ("extract", "optional bool (default True), set to False to place the downloaded file in the workspace without extracting it"),
+        ("allow_gh", "optional bool, set to False to download GitHub release urls with http instead of gh (default is `allow_gh` in .spaces/config.toml or True)"),
     ]
 }
 
@@ -102,6 +109,23 @@ checkout.add_platform_archive(
     },
 )"#;
 
+const ADD_OCI_IMAGE_DESCRIPTION: &str = r#"Pulls a single OCI image layer by digest using `oras` and extracts it
+into the workspace. Any registry that implements the OCI distribution spec works
+(for example ghcr.io, docker.io, quay.io or a private registry). Use `oras login`
+for registries that need credentials. The layer is verified against `digest`
+after it is pulled."#;
+
+const ADD_OCI_IMAGE_EXAMPLE: &str = r#"checkout.add_oci_image(
+    # the rule name is the path in the workspace where the layer will be extracted
+    rule = {"name": "toolchain"},
+    oci_image = {
+        "registry": "ghcr.io",
+        "repository": "my-org/toolchain",
+        "digest": "sha256:<layer digest>",
+        "strip_prefix": "opt/toolchain",
+    },
+)"#;
+
 const ADD_CARGO_BIN_EXAMPLE: &str = r#"checkout.add_cargo_bin(
     rule = {"name": "probe-rs-tools"},
     cargo_bin = {
@@ -111,6 +135,31 @@ const ADD_CARGO_BIN_EXAMPLE: &str = r#"checkout.add_cargo_bin(
     },
 )"#;
 
+const ADD_BINARY_EXAMPLE: &str = r#"checkout.add_binary(
+    rule = {"name": "jq"},
+    binary = {
+        "name": "jq",
+        "platforms": {
+            "macos-aarch64": {
+                "url": "https://github.com/jqlang/jq/releases/download/jq-1.7.1/jq-macos-arm64",
+                "sha256": JQ_MACOS_AARCH64_SHA256,
+            },
+            "linux-x86_64": {
+                "url": "https://github.com/jqlang/jq/releases/download/jq-1.7.1/jq-linux-amd64",
+                "sha256": JQ_LINUX_X86_64_SHA256,
+            },
+        },
+    },
+)"#;
+
+const ADD_CHECK_EXAMPLE: &str = r#"checkout.add_check(
+    rule = {"name": "clang_check", "deps": ["llvm"]},
+    exec = {
+        "command": "clang",
+        "args": ["--version"],
+    },
+)"#;
+
 const ADD_ASSET_DESCRIPTION: &str = r#"Adds a file to the workspace. This is useful for providing
 a top-level build file that orchestrates the entire workspace. It can also
 be used to create a top-level README how the workflow works."#;
@@ -153,7 +202,9 @@ const ADD_HARD_LINK_ASSET_EXAMPLE: &str = r#"checkout.add_hard_link_asset(
 
 const UPDATE_ASSET_DESCRIPTION: &str = r#"Creates or updates an existing file containing structured data
 in the workspace. This rules supports json|toml|yaml files. Different rules
-can update the same file and the content will be preserved (as long as the keys are unique)."#;
+can update the same file. Their values are deep merged in rule name order so the file
+is the same no matter which rule runs first. Two rules that set the same key to
+different values is an error that names the key path."#;
 
 const UPDATE_ASSET_EXAMPLE: &str = r#"cargo_vscode_task = {
     "type": "cargo",
@@ -167,6 +218,8 @@ checkout.update_asset(
     asset = {
         "destination": ".vscode/tasks.json",
         "format": "json",
+        # other rules can append their own tasks
+        "merge_arrays": True,
         "value": {
             "tasks": [
                 cargo_vscode_task | {
@@ -207,6 +260,9 @@ The rules allows you to add variables and paths to the environment.
 
 At a minimum, `your-workspace/sysroot/bin` should be added to the path.
 
+`PATH` is `paths` followed by `system_paths`. By default a directory that is added more than once
+is only kept where it first appears, add `PATH` to `keep_duplicates_vars` to keep every entry.
+
 In the workspace, you can start a workspace bash shell using:
 
 ```sh
@@ -248,6 +304,11 @@ pub const FUNCTIONS: &[Function] = &[
                     ("checkout", "Revision: checkout detached at commit or branch|NewBranch: create a new branch based at rev"),
                     ("clone", "Default|Worktree|Shallow"),
                     ("is_evaluate_spaces_modules", "True|False to check the repo for spaces.star files to evaluate"),
+                    ("evaluate_spaces_modules_path", "optional directory (relative to the repo) to search for spaces.star files instead of the repo root"),
+                    ("patches", "optional list of patch files (relative to the current script) applied with `git apply` after checkout"),
+                    ("dirty_policy", "Fail|Stash|Skip what to do when the repo has local changes (default is the --dirty-policy option)"),
+                    ("depth", "optional number of commits to clone (`--depth`), ignored for Worktree clones"),
+                    ("shallow_since", "optional date to clone history since (`--shallow-since`), ignored for Worktree clones. `branch:semver` revisions only see the limited history"),
                 ]
             }
         ],
@@ -267,6 +328,40 @@ pub const FUNCTIONS: &[Function] = &[
         ],
         example: Some(ADD_ARCHIVE_EXAMPLE),
     },
+    Function {
+        name: "add_binary",
+        description: "Downloads a single executable (not an archive) to the spaces store, verifies the sha256 and hardlinks it to the workspace with the executable bit set.",
+        return_type: "None",
+        args: &[
+            get_rule_argument(),
+            Arg {
+                name: "binary",
+                description: "dict with",
+                dict: &[
+                    ("url", "url of the executable (required without `platforms`)"),
+                    ("sha256", "hash of the executable or a url to the hash (required without `platforms`)"),
+                    ("platforms", "optional dict of platform name (e.g. `macos-aarch64`) to `{url, sha256}`"),
+                    ("name", "optional name of the executable in the workspace (default is the last part of the url)"),
+                    ("add_prefix", "optional workspace directory for the executable (default is sysroot/bin)"),
+                ],
+            },
+        ],
+        example: Some(ADD_BINARY_EXAMPLE),
+    },
+    Function {
+        name: "add_check",
+        description: "Adds a command that runs after all checkout rules (sysroot/bin is in the PATH). The checkout fails if the command fails. The output is written to the rule log file.",
+        return_type: "None",
+        args: &[
+            get_rule_argument(),
+            Arg {
+                name: "exec",
+                description: "dict with the same entries as exec in run.add_exec()",
+                dict: &[],
+            },
+        ],
+        example: Some(ADD_CHECK_EXAMPLE),
+    },
     Function {
         name: "add_platform_archive",
         description: "Adds an archive to the workspace based on the platform.",
@@ -286,11 +381,33 @@ pub const FUNCTIONS: &[Function] = &[
                     ("windows-x86_64", "same as macos-aarch64"),
                     ("linux-aarch64", "same as macos-aarch64"),
                     ("linux-x86_64", "same as macos-aarch64"),
+                    ("linux-riscv64", "same as macos-aarch64"),
+                    ("freebsd-x86_64", "same as macos-aarch64"),
                 ],
             },
         ],
         example: Some(ADD_PLATFORM_ARCHIVE_EXAMPLE),
     },
+    Function {
+        name: "add_oci_image",
+        description: ADD_OCI_IMAGE_DESCRIPTION,
+        return_type: "None",
+        args: &[
+            get_rule_argument(),
+            Arg {
+                name: "oci_image",
+                description: "dict with",
+                dict: &[
+                    ("registry", "registry host (e.g. ghcr.io)"),
+                    ("repository", "repository in the registry (e.g. my-org/my-image)"),
+                    ("digest", "digest of the layer to pull as sha256:<hex>"),
+                    ("strip_prefix", "optional path in the layer to extract"),
+                    ("add_prefix", "optional prefix to add in the workspace (default is the rule name)"),
+                ],
+            },
+        ],
+        example: Some(ADD_OCI_IMAGE_EXAMPLE),
+    },
     Function {
         name: "add_cargo_bin",
         description: "Adds a binary crate using cargo-binstall. The binaries are installed in the spaces store and hardlinked to the workspace.",
@@ -304,6 +421,10 @@ pub const FUNCTIONS: &[Function] = &[
                     ("crate", "The name of the binary crate"),
                     ("version", "The crate version to install"),
                     ("bins", "List of binaries to install"),
+                    ("registry", "optional name of the cargo registry to install from (passed as `--registry`)"),
+                    ("target", "optional target triple to install (passed as `--target`)"),
+                    ("extra_args", "optional list of extra `cargo-binstall` arguments. `--root`, `--version` and `--no-confirm` are set by spaces and can't be overridden"),
+                    ("sha256", "optional dict of binary name to sha256. The installed binary is verified before it is linked to the workspace"),
                 ]
             }
         ],
@@ -339,6 +460,8 @@ pub const FUNCTIONS: &[Function] = &[
                 dict: &[
                     ("content", "file contents as a string"),
                     ("destination", "relative path where asset will live in the workspace"),
+                    ("mode", "optional octal file mode such as \"0755\" (ignored on windows)"),
+                    ("substitutions", "optional dict of values that replace `{{key}}` in `content`. Setting this also enables `{{workspace}}` (absolute workspace path) and `{{rule}}` (rule name). Use `\\{{` for a literal `{{`"),
                 ],
             },
         ],
@@ -372,6 +495,7 @@ pub const FUNCTIONS: &[Function] = &[
                     dict: &[
                         ("source", "the source of the hard link"),
                         ("destination", "relative path where asset will live in the workspace"),
+                        ("mode", "optional octal file mode such as \"0755\" (ignored on windows). The source shares the mode with the hard link"),
                     ],
                 },
             ],
@@ -407,6 +531,7 @@ pub const FUNCTIONS: &[Function] = &[
                     ("destination", "path to the asset in the workspace"),
                     ("format", "json|toml|yaml"),
                     ("value", "dict containing the structured data to be added to the asset"),
+                    ("merge_arrays", "optional bool, set to True to append the items of this rule's arrays to arrays set by other rules (in rule name order) instead of requiring them to be equal"),
                 ],
             },
         ],
@@ -423,8 +548,15 @@ pub const FUNCTIONS: &[Function] = &[
                 dict: &[
                     ("vars", "dict of variables to add to the environment"),
                     ("paths", "list of paths required"),
+                    ("secret_vars", "optional list of variable names whose values are replaced with `REDACTED` in the logs"),
+                    ("keep_duplicates_vars", "optional list of list variable names (e.g. `PATH`) that keep repeated entries (default only the first occurrence is kept)"),
                 ],
             },
+            Arg {
+                name: "dotenv_files",
+                description: "optional list of KEY=VALUE dotenv files (relative to the current script) read at checkout. Each variable is added to the environment unless `vars` sets it",
+                dict: &[],
+            },
         ],
         example: Some(UPDATE_ENV_EXAMPLE)},
     Function {
@@ -456,8 +588,59 @@ struct CargoBin {
     crate_: String,
     bins: Vec<String>,
     version: String,
+    sha256: Option<std::collections::HashMap<String, std::sync::Arc<str>>>,
+    registry: Option<String>,
+    target: Option<String>,
+    extra_args: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+struct BinaryDownload {
+    url: std::sync::Arc<str>,
+    sha256: std::sync::Arc<str>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+struct Binary {
+    url: Option<std::sync::Arc<str>>,
+    sha256: Option<std::sync::Arc<str>>,
+    platforms: Option<std::collections::HashMap<String, BinaryDownload>>,
+    name: Option<String>,
+    add_prefix: Option<String>,
+}
+
+impl Binary {
+    /// The download for `platform_name` when `platforms` is given, otherwise `url` and `sha256`
+    fn get_download(self, rule_name: &str, platform_name: &str) -> anyhow::Result<BinaryDownload> {
+        if let Some(mut platforms) = self.platforms {
+            platforms.remove(platform_name).ok_or(format_error!(
+                "Platform {platform_name} not supported by {rule_name}"
+            ))
+        } else if let (Some(url), Some(sha256)) = (self.url, self.sha256) {
+            Ok(BinaryDownload { url, sha256 })
+        } else {
+            Err(format_error!(
+                "{rule_name} needs `url` and `sha256` or `platforms`"
+            ))
+        }
+    }
 }
 
+/// The last path segment of `url` without the query or fragment
+fn get_url_file_name(url: &str) -> anyhow::Result<String> {
+    url.split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|file_name| !file_name.is_empty())
+        .map(|file_name| file_name.to_string())
+        .ok_or(format_error!("No file name found in url {url}"))
+}
+
+// spaces sets these so the binaries land in the store directory for the version
+const CARGO_BIN_RESERVED_ARGS: &[&str] = &["--root", "--version", "--no-confirm"];
+
 // This defines the function that is visible to Starlark
 #[starlark_module]
 pub fn globals(builder: &mut GlobalsBuilder) {
@@ -501,6 +684,43 @@ pub fn globals(builder: &mut GlobalsBuilder) {
             workspace.get_absolute_path()
         };
 
+        let mut patches = None;
+        if let Some(patch_paths) = repo.patches.as_ref() {
+            let checkout_path = rules::get_checkout_path()
+                .context(format_context!("Failed to get the current script path"))?;
+            let mut resolved = Vec::new();
+            for patch_path in patch_paths {
+                let path: std::sync::Arc<str> = if checkout_path.is_empty() {
+                    format!("{}/{patch_path}", workspace.get_absolute_path()).into()
+                } else {
+                    format!("{}/{checkout_path}/{patch_path}", workspace.get_absolute_path())
+                        .into()
+                };
+                let digest = changes::get_file_digest(std::path::Path::new(path.as_ref()))
+                    .context(format_context!("Failed to read patch {path}"))?;
+                resolved.push(executor::git::Patch { path, digest });
+            }
+            patches = Some(resolved);
+        }
+
+        if let Some(modules_path) = repo.evaluate_spaces_modules_path.as_ref() {
+            if repo.is_evaluate_spaces_modules == Some(false) {
+                return Err(format_error!(
+                    "{}: evaluate_spaces_modules_path is set but is_evaluate_spaces_modules is False",
+                    rule.name
+                ));
+            }
+            let path = std::path::Path::new(modules_path.as_ref());
+            if path.is_absolute()
+                || path.components().any(|item| item == std::path::Component::ParentDir)
+            {
+                return Err(format_error!(
+                    "{}: evaluate_spaces_modules_path {modules_path} must be inside the repo",
+                    rule.name
+                ));
+            }
+        }
+
         let checkout = repo.get_checkout();
         let spaces_key = rule.name.clone();
         let rule_name = rule.name.clone();
@@ -514,8 +734,13 @@ pub fn globals(builder: &mut GlobalsBuilder) {
                 checkout,
                 clone: repo.clone.unwrap_or(git::Clone::Default),
                 is_evaluate_spaces_modules: repo.is_evaluate_spaces_modules.unwrap_or(true),
+                evaluate_spaces_modules_path: repo.evaluate_spaces_modules_path,
                 sparse_checkout: repo.sparse_checkout,
-                working_directory: repo.working_directory
+                working_directory: repo.working_directory,
+                patches,
+                dirty_policy: repo.dirty_policy,
+                depth: repo.depth,
+                shallow_since: repo.shallow_since,
             }),
         ))
         .context(format_context!("Failed to insert task {rule_name}"))?;
@@ -546,23 +771,51 @@ pub fn globals(builder: &mut GlobalsBuilder) {
 
         let hard_link_rule = rule.clone();
 
+        let mut bin_sha256 = cargo_bin.sha256.unwrap_or_default();
+        if let Some(unknown_bin) = bin_sha256.keys().find(|bin| !cargo_bin.bins.contains(bin)) {
+            return Err(format_error!(
+                "sha256 is given for {unknown_bin} which is not in bins of {}",
+                rule.name
+            ));
+        }
+
         let cargo_binstall_path = format!(
             "{}/sysroot/bin/cargo-binstall",
             workspace.get_spaces_tools_path()
         );
 
+        let mut args: Vec<std::sync::Arc<str>> = vec![
+            format!("--version={}", cargo_bin.version).into(),
+            format!("--root={output_directory}").into(),
+            "--no-confirm".into(),
+        ];
+        if let Some(registry) = cargo_bin.registry.as_ref() {
+            args.push(format!("--registry={registry}").into());
+        }
+        if let Some(target) = cargo_bin.target.as_ref() {
+            args.push(format!("--target={target}").into());
+        }
+        for arg in cargo_bin.extra_args.iter().flatten() {
+            let name = arg.split('=').next().unwrap_or_default();
+            if CARGO_BIN_RESERVED_ARGS.contains(&name) {
+                return Err(format_error!(
+                    "extra_args for {} can't override {name}, spaces sets it",
+                    rule.name
+                ));
+            }
+            args.push(arg.as_str().into());
+        }
+        args.push(cargo_bin.crate_.into());
+
         let exec = executor::exec::Exec {
             command: cargo_binstall_path.into(),
-            args: Some(vec![
-                format!("--version={}", cargo_bin.version).into(),
-                format!("--root={output_directory}").into(),
-                "--no-confirm".into(),
-                cargo_bin.crate_.into(),
-            ]),
+            args: Some(args),
             env: None,
             working_directory: None,
             redirect_stdout: None,
             expect: None,
+            env_files: None,
+            env_mode: None,
         };
 
         let rule_name = rule.name.clone();
@@ -587,6 +840,8 @@ pub fn globals(builder: &mut GlobalsBuilder) {
                 executor::Task::AddHardLink(asset::AddHardLink {
                     source: output_file,
                     destination: format!("sysroot/bin/{}", bin),
+                    mode: None,
+                    sha256: bin_sha256.remove(&bin),
                 }),
             ))
             .context(format_context!("Failed to insert task {rule_name}"))?;
@@ -595,6 +850,97 @@ pub fn globals(builder: &mut GlobalsBuilder) {
         Ok(NoneType)
     }
 
+    fn add_binary(
+        #[starlark(require = named)] rule: starlark::values::Value,
+        #[starlark(require = named)] binary: starlark::values::Value,
+    ) -> anyhow::Result<NoneType> {
+        let rule: rules::Rule = serde_json::from_value(rule.to_json_value()?)
+            .context(format_context!("bad options for add_binary rule"))?;
+        let binary: Binary = serde_json::from_value(binary.to_json_value()?)
+            .context(format_context!("bad options for binary"))?;
+
+        let platform_name = platform::Platform::get_platform()
+            .map(|p| p.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let name = binary.name.clone();
+        let add_prefix = binary.add_prefix.clone();
+        let download = binary.get_download(rule.name.as_ref(), platform_name.as_str())?;
+
+        let file_name = get_url_file_name(download.url.as_ref())?;
+        let name = name.unwrap_or_else(|| file_name.clone());
+        let add_prefix = add_prefix.unwrap_or_else(|| "sysroot/bin".to_string());
+
+        let archive = http_archive::Archive {
+            url: download.url.clone(),
+            sha256: download.sha256.clone(),
+            link: http_archive::ArchiveLink::None,
+            extract: Some(false),
+            ..Default::default()
+        };
+
+        let workspace_arc =
+            singleton::get_workspace().context(format_error!("No active workspace found"))?;
+        let workspace = workspace_arc.read();
+        let mut http_archive = http_archive::HttpArchive::new(
+            &workspace.get_store_path(),
+            rule.name.as_ref(),
+            &archive,
+            format!("{}/sysroot/bin", workspace.get_spaces_tools_path()).as_str(),
+        )
+        .context(format_context!(
+            "Failed to create http_archive {}",
+            rule.name
+        ))?;
+        http_archive.allow_gh_for_download(singleton::get_allow_gh());
+        let source = format!("{}/{file_name}", http_archive.get_path_to_extracted_files());
+        // `new` resolves a sha256 url, the link checks the hash again
+        let sha256 = http_archive.get_sha256();
+
+        let mut link_rule = rule.clone();
+        link_rule.name = format!("{}/{name}", rule.name).into();
+
+        let rule_name = rule.name.clone();
+        rules::insert_task(rules::Task::new(
+            rule,
+            rules::Phase::Checkout,
+            executor::Task::HttpArchive(executor::http_archive::HttpArchive { http_archive }),
+        ))
+        .context(format_context!("Failed to insert task {rule_name}"))?;
+
+        rules::insert_task(rules::Task::new(
+            link_rule,
+            rules::Phase::PostCheckout,
+            executor::Task::AddHardLink(asset::AddHardLink {
+                source,
+                destination: format!("{add_prefix}/{name}"),
+                mode: Some("0755".to_string()),
+                sha256: Some(sha256),
+            }),
+        ))
+        .context(format_context!("Failed to insert task {rule_name}"))?;
+
+        Ok(NoneType)
+    }
+
+    fn add_check(
+        #[starlark(require = named)] rule: starlark::values::Value,
+        #[starlark(require = named)] exec: starlark::values::Value,
+    ) -> anyhow::Result<NoneType> {
+        let rule: rules::Rule = serde_json::from_value(rule.to_json_value()?)
+            .context(format_context!("bad options for check rule"))?;
+        let exec: executor::exec::Exec = serde_json::from_value(exec.to_json_value()?)
+            .context(format_context!("bad options for check exec"))?;
+
+        let rule_name = rule.name.clone();
+        rules::insert_task(rules::Task::new(
+            rule,
+            rules::Phase::PostCheckout,
+            executor::Task::Exec(exec),
+        ))
+        .context(format_context!("Failed to insert task {rule_name}"))?;
+        Ok(NoneType)
+    }
+
     fn add_platform_archive(
         #[starlark(require = named)] rule: starlark::values::Value,
         #[starlark(require = named)] platforms: starlark::values::Value,
@@ -612,13 +958,17 @@ pub fn globals(builder: &mut GlobalsBuilder) {
             Some(platform::Platform::WindowsAarch64) => platforms.windows_aarch64,
             Some(platform::Platform::LinuxX86_64) => platforms.linux_x86_64,
             Some(platform::Platform::LinuxAarch64) => platforms.linux_aarch64,
+            Some(platform::Platform::LinuxRiscv64) => platforms.linux_riscv64,
+            Some(platform::Platform::FreebsdX86_64) => platforms.freebsd_x86_64,
             _ => None,
         };
 
         if platform_archive.is_none() {
+            let platform_name = platform::Platform::get_platform()
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "unknown".to_string());
             return Err(format_error!(
-                "Platform {} not supported by {}",
-                platform::Platform::get_platform().unwrap(),
+                "Platform {platform_name} not supported by {}",
                 rule.name
             ));
         }
@@ -658,6 +1008,9 @@ pub fn globals(builder: &mut GlobalsBuilder) {
 
         let asset: asset::AddHardLink = serde_json::from_value(asset.to_json_value()?)
             .context(format_context!("Failed to parse which asset arguments"))?;
+        if let Some(mode) = asset.mode.as_ref() {
+            asset::parse_mode(mode).context(format_context!("bad mode for {}", asset.destination))?;
+        }
 
         let rule_name = rule.name.clone();
         rules::insert_task(rules::Task::new(
@@ -729,6 +1082,28 @@ pub fn globals(builder: &mut GlobalsBuilder) {
         Ok(NoneType)
     }
 
+    fn add_oci_image(
+        #[starlark(require = named)] rule: starlark::values::Value,
+        #[starlark(require = named)] oci_image: starlark::values::Value,
+    ) -> anyhow::Result<NoneType> {
+        let rule: rules::Rule = serde_json::from_value(rule.to_json_value()?)
+            .context(format_context!("bad options for oci image rule"))?;
+
+        let oci_image: executor::oras::OciImage =
+            serde_json::from_value(oci_image.to_json_value()?)
+                .context(format_context!("Failed to parse oci image arguments"))?;
+
+        let rule_name = rule.name.clone();
+        rules::insert_task(rules::Task::new(
+            rule,
+            rules::Phase::Checkout,
+            executor::Task::OciImage(oci_image),
+        ))
+        .context(format_context!("Failed to insert task {rule_name}"))?;
+
+        Ok(NoneType)
+    }
+
     fn add_asset(
         #[starlark(require = named)] rule: starlark::values::Value,
         #[starlark(require = named)] asset: starlark::values::Value,
@@ -739,6 +1114,10 @@ pub fn globals(builder: &mut GlobalsBuilder) {
         let add_asset: executor::asset::AddAsset =
             serde_json::from_value(asset.to_json_value()?)
                 .context(format_context!("Failed to parse asset arguments"))?;
+        if let Some(mode) = add_asset.mode.as_ref() {
+            executor::asset::parse_mode(mode)
+                .context(format_context!("bad mode for {}", add_asset.destination))?;
+        }
 
         let rule_name = rule.name.clone();
         rules::insert_task(rules::Task::new(
@@ -803,13 +1182,43 @@ pub fn globals(builder: &mut GlobalsBuilder) {
     fn update_env(
         #[starlark(require = named)] rule: starlark::values::Value,
         #[starlark(require = named)] env: starlark::values::Value,
+        #[starlark(require = named)] dotenv_files: Option<starlark::values::Value>,
     ) -> anyhow::Result<NoneType> {
         let rule: rules::Rule = serde_json::from_value(rule.to_json_value()?)
             .context(format_context!("bad options for update env rule"))?;
 
         // support JSON, yaml, and toml
-        let environment: environment::Environment = serde_json::from_value(env.to_json_value()?)
-            .context(format_context!("Failed to parse archive arguments"))?;
+        let mut environment: environment::Environment =
+            serde_json::from_value(env.to_json_value()?)
+                .context(format_context!("Failed to parse archive arguments"))?;
+
+        if let Some(dotenv_files) = dotenv_files {
+            let dotenv_files: Vec<std::sync::Arc<str>> =
+                serde_json::from_value(dotenv_files.to_json_value()?)
+                    .context(format_context!("dotenv_files must be a list of paths"))?;
+            let workspace_path = singleton::get_workspace()
+                .context(format_error!("No active workspace found"))?
+                .read()
+                .get_absolute_path();
+            let checkout_path = rules::get_checkout_path()
+                .context(format_context!("Failed to get the current script path"))?;
+
+            // the files are read during checkout, vars in `env` take precedence
+            for dotenv_file in dotenv_files {
+                let path = if checkout_path.is_empty() {
+                    format!("{workspace_path}/{dotenv_file}")
+                } else {
+                    format!("{workspace_path}/{checkout_path}/{dotenv_file}")
+                };
+                let content = std::fs::read_to_string(path.as_str())
+                    .context(format_context!("Failed to read dotenv file {path}"))?;
+                let vars = environment::parse_dotenv(content.as_str())
+                    .context(format_context!("Malformed dotenv file {path}"))?;
+                for (key, value) in vars {
+                    environment.vars.entry(key).or_insert(value);
+                }
+            }
+        }
 
         let update_env = executor::env::UpdateEnv { environment };
 
@@ -870,7 +1279,7 @@ fn add_http_archive(
             singleton::get_workspace().context(format_error!("No active workspace found"))?;
         let workspace = workspace_arc.read();
 
-        let http_archive = http_archive::HttpArchive::new(
+        let mut http_archive = http_archive::HttpArchive::new(
             &workspace.get_store_path(),
             rule.name.as_ref(),
             &archive,
@@ -880,6 +1289,8 @@ fn add_http_archive(
             "Failed to create http_archive {}",
             rule.name
         ))?;
+        let allow_gh = archive.allow_gh.unwrap_or(singleton::get_allow_gh());
+        http_archive.allow_gh_for_download(allow_gh);
 
         let rule_name = rule.name.clone();
         rules::insert_task(rules::Task::new(
@@ -891,3 +1302,46 @@ fn add_http_archive(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_binary(value: serde_json::Value) -> anyhow::Result<Binary> {
+        Ok(serde_json::from_value(value)?)
+    }
+
+    #[test]
+    fn binary_download_by_platform_or_url() {
+        let binary = parse_binary(serde_json::json!({
+            "name": "jq",
+            "platforms": {
+                "linux-x86_64": {"url": "https://example.com/jq-linux", "sha256": "aa"},
+                "macos-aarch64": {"url": "https://example.com/jq-macos", "sha256": "bb"},
+            },
+        }))
+        .unwrap();
+        let download = binary.clone().get_download("jq", "macos-aarch64").unwrap();
+        assert_eq!(download.url.as_ref(), "https://example.com/jq-macos");
+        assert_eq!(download.sha256.as_ref(), "bb");
+        assert!(binary.get_download("jq", "windows-x86_64").is_err());
+
+        let binary = parse_binary(serde_json::json!({
+            "url": "https://example.com/download/tool?version=1#latest",
+            "sha256": "https://example.com/download/tool.sha256",
+        }))
+        .unwrap();
+        let download = binary.get_download("tool", "linux-x86_64").unwrap();
+        // a sha256 url is resolved by HttpArchive::new, not while parsing
+        assert_eq!(
+            download.sha256.as_ref(),
+            "https://example.com/download/tool.sha256"
+        );
+        assert_eq!(get_url_file_name(download.url.as_ref()).unwrap(), "tool");
+
+        let binary = parse_binary(serde_json::json!({"url": "https://example.com/tool"})).unwrap();
+        assert!(binary.get_download("tool", "linux-x86_64").is_err());
+        assert!(parse_binary(serde_json::json!({"url": "u", "sha": "s"})).is_err());
+        assert!(get_url_file_name("https://example.com/download/").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{rules, singleton};
+use crate::{executor, rules, singleton};
 use anyhow::Context;
 use anyhow_source_location::{format_context, format_error};
 use starlark::environment::GlobalsBuilder;
@@ -7,10 +7,34 @@ use starlark::values::{Heap, Value};
 use starstd::{Arg, Function};
 use std::collections::HashMap;
 
+fn resolve_workspace_path(workspace_path: &str, path: &str) -> anyhow::Result<String> {
+    let mut parts: Vec<&str> = Vec::new();
+    for component in std::path::Path::new(path).components() {
+        match component {
+            std::path::Component::Normal(part) => {
+                parts.push(part.to_str().ok_or(format_error!("{path} is not valid UTF-8"))?)
+            }
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                if parts.pop().is_none() {
+                    return Err(format_error!("{path} is outside of the workspace"));
+                }
+            }
+            std::path::Component::RootDir | std::path::Component::Prefix(_) => {
+                return Err(format_error!("{path} must be relative to the workspace"));
+            }
+        }
+    }
+    if parts.is_empty() {
+        return Ok(workspace_path.to_string());
+    }
+    Ok(format!("{workspace_path}/{}", parts.join("/")))
+}
+
 pub const FUNCTIONS: &[Function] = &[
     Function {
         name: "get_platform_name",
-        description: "returns the name of the current platform: macos-aarch64|macos-x86_64|linux-x86_64|linux-aarch64|windows-x86_64|windows-aarch64",
+        description: "returns the name of the current platform: macos-aarch64|macos-x86_64|linux-x86_64|linux-aarch64|linux-riscv64|windows-x86_64|windows-aarch64|freebsd-x86_64. Set `SPACES_PLATFORM` to override the detected platform.",
         return_type: "str",
         args: &[],
         example: None,
@@ -57,6 +81,19 @@ pub const FUNCTIONS: &[Function] = &[
         args: &[],
         example: None,
     },
+    Function {
+        name: "resolve_path",
+        description: "returns the absolute path for a path relative to the workspace root. Fails if the path is absolute or leaves the workspace.",
+        return_type: "str",
+        args: &[
+            Arg {
+                name: "path",
+                description: "The path relative to the workspace root",
+                dict: &[],
+            },
+        ],
+        example: Some(r#"working_directory = info.resolve_path("build/sysroot")"#),
+    },
     Function {
         name: "get_env_var",
         description: "returns the path where the current script is located in the workspace",
@@ -156,7 +193,7 @@ pub const FUNCTIONS: &[Function] = &[
     },
     Function {
         name: "set_max_queue_count",
-        description: "sets the maxiumum number of items to queue at one time",
+        description: "sets the maxiumum number of items to queue at one time, `-j/--jobs` on the command line takes precedence",
         return_type: "int",
         args: &[
             Arg {
@@ -167,8 +204,52 @@ pub const FUNCTIONS: &[Function] = &[
         ],
         example: None,
     },
+    Function {
+        name: "set_resource_capacity",
+        description: "sets how many rules that use the named `resource` can run at one time (default is 1)",
+        return_type: "None",
+        args: &[
+            Arg {
+                name: "resource",
+                description: "the name of the resource (e.g. `link`)",
+                dict: &[],
+            },
+            Arg {
+                name: "capacity",
+                description: "the maximum number of rules using the resource to run at one time",
+                dict: &[],
+            },
+        ],
+        example: None,
+    },
+    Function {
+        name: "set_run_hooks",
+        description: r#"sets commands that run once before and after the rules of `spaces run`.
+The pre hook runs after the scripts are evaluated and before any rule (including `//:setup`). If it fails, no rules run.
+The post hook runs after all rules complete, even if the pre hook or a rule failed. Hooks do not run with `--dry-run`.
+Unlike `//:setup`, hooks are not rules: they are not targets, have no inputs and are never skipped."#,
+        return_type: "None",
+        args: &[
+            Arg {
+                name: "pre",
+                description: "optional exec dict (same as `run.add_exec()`) to run before the rules",
+                dict: &[],
+            },
+            Arg {
+                name: "post",
+                description: "optional exec dict (same as `run.add_exec()`) to run after the rules",
+                dict: &[],
+            },
+        ],
+        example: Some(SET_RUN_HOOKS_EXAMPLE),
+    },
 ];
 
+const SET_RUN_HOOKS_EXAMPLE: &str = r#"info.set_run_hooks(
+    pre = {"command": "docker", "args": ["compose", "up", "-d"]},
+    post = {"command": "docker", "args": ["compose", "down"]},
+)"#;
+
 #[starlark_module]
 pub fn globals(builder: &mut GlobalsBuilder) {
     // remove and replace with get_path_to_store()
@@ -364,6 +445,14 @@ pub fn globals(builder: &mut GlobalsBuilder) {
         rules::get_checkout_path().map(|p| p.to_string())
     }
 
+    fn resolve_path(path: &str) -> anyhow::Result<String> {
+        let workspace_arc =
+            singleton::get_workspace().context(format_error!("No active workspace found"))?;
+        let workspace_path = workspace_arc.read().get_absolute_path();
+        resolve_workspace_path(workspace_path.as_ref(), path)
+            .context(format_context!("Failed to resolve path {path}"))
+    }
+
     fn get_path_to_build_checkout(
         #[starlark(require = named)] rule_name: &str,
     ) -> anyhow::Result<String> {
@@ -459,4 +548,58 @@ pub fn globals(builder: &mut GlobalsBuilder) {
         singleton::set_max_queue_count(count);
         Ok(NoneType)
     }
+
+    fn set_run_hooks(
+        #[starlark(require = named)] pre: Option<Value>,
+        #[starlark(require = named)] post: Option<Value>,
+    ) -> anyhow::Result<NoneType> {
+        let pre: Option<executor::exec::Exec> = match pre {
+            Some(pre) => Some(
+                serde_json::from_value(pre.to_json_value()?)
+                    .context(format_context!("bad options for pre run hook"))?,
+            ),
+            None => None,
+        };
+        let post: Option<executor::exec::Exec> = match post {
+            Some(post) => Some(
+                serde_json::from_value(post.to_json_value()?)
+                    .context(format_context!("bad options for post run hook"))?,
+            ),
+            None => None,
+        };
+        singleton::set_run_hooks(pre, post);
+        Ok(NoneType)
+    }
+
+    fn set_resource_capacity(
+        #[starlark(require = named)] resource: &str,
+        #[starlark(require = named)] capacity: i64,
+    ) -> anyhow::Result<NoneType> {
+        if capacity < 1 {
+            return Err(anyhow::anyhow!("resource capacity must be greater than 0"));
+        }
+        singleton::set_resource_capacity(resource.into(), capacity);
+        Ok(NoneType)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_paths_inside_the_workspace() {
+        let workspace = "/home/user/workspace";
+        assert_eq!(
+            resolve_workspace_path(workspace, "build/./sysroot").unwrap(),
+            "/home/user/workspace/build/sysroot"
+        );
+        assert_eq!(
+            resolve_workspace_path(workspace, "a/../b").unwrap(),
+            "/home/user/workspace/b"
+        );
+        assert_eq!(resolve_workspace_path(workspace, ".").unwrap(), workspace);
+        assert!(resolve_workspace_path(workspace, "a/../../b").is_err());
+        assert!(resolve_workspace_path(workspace, "/etc").is_err());
+    }
 }
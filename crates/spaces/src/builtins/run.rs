@@ -53,7 +53,9 @@ pub const FUNCTIONS: &[Function] = &[
                 dict: &[
                     ("command", "name of the command to execute"),
                     ("args", "optional list of arguments"),
-                    ("env", "optional dict of environment variables"),
+                    ("env", "optional dict of environment variables. `WORKSPACE` is always set to the absolute path of the workspace (use `$WORKSPACE` in commands run by a shell), it is resolved when the rule runs so it does not change the rule digest"),
+                    ("env_files", "optional list of KEY=VALUE files (workspace relative) to load into the environment. Precedence is workspace env < env_files < env < WORKSPACE"),
+                    ("env_mode", "Extend (default): the workspace env is inherited and overridden by env_files and env|Replace: only PATH, HOME (if inherited by the workspace) and WORKSPACE are kept before env_files and env are applied. Secret vars are redacted in both modes"),
                     ("working_directory", "optional working directory (default is the workspace)"),
                     ("expect", "Failure: expect non-zero return code|Success: expect zero return code|Any: don't check the return code"),
                     ("redirect_stdout", "optional file to redirect stdout to"),
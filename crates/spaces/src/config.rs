@@ -0,0 +1,128 @@
+use crate::{arguments, workspace};
+use anyhow::Context;
+use anyhow_source_location::format_context;
+use serde::Deserialize;
+use std::sync::Arc;
+
+const CONFIG_FILE_NAME: &str = ".spaces/config.toml";
+
+/// Defaults for CLI options loaded from `.spaces/config.toml` in the workspace
+/// and in the home directory (`$HOME/.spaces/config.toml`).
+///
+/// Precedence is: CLI flag > workspace config > env > user config > built-in default.
+/// `SPACES_HOME` is the only env var, it overrides the user config `store_path`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub verbosity: Option<arguments::Level>,
+    pub hide_progress_bars: Option<bool>,
+    pub ci: Option<bool>,
+    pub jobs: Option<i64>,
+    pub store_path: Option<Arc<str>>,
+    /// Set to false to download GitHub release archives with http instead of gh
+    pub allow_gh: Option<bool>,
+    /// How many archives are downloaded at the same time
+    pub max_concurrent_downloads: Option<usize>,
+}
+
+impl Config {
+    /// Loads the workspace config and fills the unset values from the user config.
+    pub fn load() -> anyhow::Result<Self> {
+        let workspace_config = Self::load_from_current_directory()
+            .context(format_context!("Failed to load workspace config"))?;
+        let user_config =
+            Self::load_from_home().context(format_context!("Failed to load user config"))?;
+        let is_spaces_home = std::env::var(workspace::SPACES_HOME_ENV_VAR).is_ok();
+        Ok(workspace_config.or(user_config, is_spaces_home))
+    }
+
+    fn or(self, user_config: Self, is_spaces_home: bool) -> Self {
+        Self {
+            verbosity: self.verbosity.or(user_config.verbosity),
+            hide_progress_bars: self.hide_progress_bars.or(user_config.hide_progress_bars),
+            ci: self.ci.or(user_config.ci),
+            jobs: self.jobs.or(user_config.jobs),
+            store_path: self
+                .store_path
+                .or(user_config.store_path.filter(|_| !is_spaces_home)),
+            allow_gh: self.allow_gh.or(user_config.allow_gh),
+            max_concurrent_downloads: self
+                .max_concurrent_downloads
+                .or(user_config.max_concurrent_downloads),
+        }
+    }
+
+    /// Loads `$HOME/.spaces/config.toml`.
+    /// Returns the default config if there is no home directory or no config file.
+    fn load_from_home() -> anyhow::Result<Self> {
+        let Ok(Some(home_path)) = homedir::my_home() else {
+            return Ok(Self::default());
+        };
+        Self::load_file(format!("{}/{CONFIG_FILE_NAME}", home_path.to_string_lossy()).as_str())
+    }
+
+    /// Loads the config for the workspace containing the current directory.
+    /// Returns the default config if there is no workspace or no config file.
+    pub fn load_from_current_directory() -> anyhow::Result<Self> {
+        let Ok(current_directory) = std::env::current_dir() else {
+            return Ok(Self::default());
+        };
+        let Ok(workspace_root) =
+            workspace::Workspace::find_workspace_root(current_directory.to_string_lossy().as_ref())
+        else {
+            return Ok(Self::default());
+        };
+
+        Self::load_file(format!("{workspace_root}/{CONFIG_FILE_NAME}").as_str())
+    }
+
+    fn load_file(config_path: &str) -> anyhow::Result<Self> {
+        if !std::path::Path::new(config_path).exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(config_path)
+            .context(format_context!("Failed to read {config_path}"))?;
+        toml::from_str(content.as_str()).context(format_context!("Failed to parse {config_path}"))
+    }
+}
+
+pub fn resolve<Value>(cli: Option<Value>, config: Option<Value>, default: Value) -> Value {
+    cli.or(config).unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_overrides_config_overrides_default() {
+        assert_eq!(resolve(Some(4), Some(2), 8), 4);
+        assert_eq!(resolve(None, Some(2), 8), 2);
+        assert_eq!(resolve(None, None, 8), 8);
+    }
+
+    #[test]
+    fn config_parses_partial_defaults() {
+        let config: Config = toml::from_str("verbosity = \"debug\"\njobs = 4\n").unwrap();
+        assert!(matches!(config.verbosity, Some(arguments::Level::Debug)));
+        assert_eq!(config.jobs, Some(4));
+        assert_eq!(config.ci, None);
+        assert!(toml::from_str::<Config>("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn workspace_config_overrides_user_config() {
+        let workspace_config: Config = toml::from_str("jobs = 4\n").unwrap();
+        let user_config: Config =
+            toml::from_str("jobs = 2\nci = true\nstore_path = \"/store\"\n").unwrap();
+        let config = workspace_config.or(user_config, false);
+        assert_eq!(config.jobs, Some(4));
+        assert_eq!(config.ci, Some(true));
+        assert_eq!(config.store_path.as_deref(), Some("/store"));
+
+        // SPACES_HOME takes precedence over the user config store path
+        let user_config: Config = toml::from_str("store_path = \"/store\"\n").unwrap();
+        assert_eq!(Config::default().or(user_config, true).store_path, None);
+    }
+}
@@ -187,6 +187,8 @@ fn show_all(markdown: &mut printer::markdown::Markdown) -> anyhow::Result<()> {
         "`linux-x86_64`",
         "`windows-aarch64`",
         "`windows-x86_64`",
+        "`linux-riscv64`",
+        "`freebsd-x86_64`",
     ])?;
     markdown.printer.newline()?;
 
@@ -0,0 +1,146 @@
+use crate::workspace;
+use anyhow_source_location::format_error;
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    is_passed: bool,
+    is_critical: bool,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<&'static str>,
+}
+
+impl Check {
+    fn new(name: &'static str, is_critical: bool, result: Result<String, String>) -> Self {
+        match result {
+            Ok(detail) => Self {
+                name,
+                is_passed: true,
+                is_critical,
+                detail,
+                hint: None,
+            },
+            Err(detail) => Self {
+                name,
+                is_passed: false,
+                is_critical,
+                detail,
+                hint: None,
+            },
+        }
+    }
+
+    fn with_hint(mut self, hint: &'static str) -> Self {
+        if !self.is_passed {
+            self.hint = Some(hint);
+        }
+        self
+    }
+}
+
+fn check_git() -> Result<String, String> {
+    let output = std::process::Command::new("git")
+        .arg("--version")
+        .output()
+        .map_err(|error| format!("failed to run git: {error}"))?;
+    if !output.status.success() {
+        return Err(format!("git --version exited with {}", output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn check_gh(store_path: &str) -> Result<String, String> {
+    let tools_gh = format!("{}/sysroot/bin/gh", workspace::get_spaces_tools_path(store_path));
+    if std::path::Path::new(tools_gh.as_str()).exists() {
+        return Ok(tools_gh);
+    }
+    which::which("gh")
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|_| "gh was not found in the spaces tools or on the PATH".to_string())
+}
+
+fn check_store_writable(store_path: &str) -> Result<String, String> {
+    std::fs::create_dir_all(store_path)
+        .map_err(|error| format!("failed to create {store_path}: {error}"))?;
+    let probe_path = format!("{store_path}/doctor-{}.tmp", std::process::id());
+    std::fs::write(probe_path.as_str(), "spaces doctor")
+        .map_err(|error| format!("failed to write to {store_path}: {error}"))?;
+    let _ = std::fs::remove_file(probe_path.as_str());
+    Ok(store_path.to_string())
+}
+
+fn check_store_hard_link(store_path: &str) -> Result<String, String> {
+    // archives are hard linked from the store into the workspace
+    let source_path = format!("{store_path}/doctor-{}.src.tmp", std::process::id());
+    let link_path = format!("{store_path}/doctor-{}.link.tmp", std::process::id());
+    std::fs::write(source_path.as_str(), "spaces doctor")
+        .map_err(|error| format!("failed to write to {store_path}: {error}"))?;
+    let result = std::fs::hard_link(source_path.as_str(), link_path.as_str())
+        .map(|_| "hard links are supported".to_string())
+        .map_err(|error| format!("failed to create a hard link in {store_path}: {error}"));
+    let _ = std::fs::remove_file(link_path.as_str());
+    let _ = std::fs::remove_file(source_path.as_str());
+    result
+}
+
+fn check_settings() -> Result<String, String> {
+    let current_directory = std::env::current_dir()
+        .map_err(|error| format!("failed to get the current directory: {error}"))?;
+    let Ok(workspace_root) =
+        workspace::Workspace::find_workspace_root(current_directory.to_string_lossy().as_ref())
+    else {
+        return Ok("not in a workspace".to_string());
+    };
+    let settings_path = format!("{workspace_root}/{}", workspace::SETTINGS_FILE_NAME);
+    if !std::path::Path::new(settings_path.as_str()).exists() {
+        return Ok(format!("{workspace_root} has no settings file"));
+    }
+    workspace::Settings::load(workspace_root.as_ref())
+        .map(|_| format!("{workspace_root} settings are valid"))
+        .map_err(|error| format!("{error:#}"))
+}
+
+/// Checks the tools and directories spaces needs and returns an error if
+/// any critical check fails.
+pub fn run(printer: &mut printer::Printer) -> anyhow::Result<()> {
+    let store_path: Arc<str> = workspace::get_checkout_store_path();
+    let is_store_writable = check_store_writable(store_path.as_ref());
+    let is_store_hard_link = if is_store_writable.is_ok() {
+        check_store_hard_link(store_path.as_ref())
+    } else {
+        Err("skipped because the store is not writable".to_string())
+    };
+
+    let checks = vec![
+        Check::new("git", true, check_git())
+            .with_hint("install git and make sure it is on the PATH"),
+        Check::new("gh", false, check_gh(store_path.as_ref())).with_hint(
+            "run `spaces checkout --force-install-tools` or install gh, it is used to download archives from GitHub releases (set `allow_gh = false` in .spaces/config.toml to use http instead)",
+        ),
+        Check::new("store", true, is_store_writable).with_hint(
+            "make sure the store directory is writable or move it with SPACES_HOME or `store_path` in .spaces/config.toml",
+        ),
+        Check::new("hard links", false, is_store_hard_link).with_hint(
+            "on macOS enable 'Full Disk Access' for the terminal, otherwise keep the store and workspace on the same file system",
+        ),
+        Check::new("settings", true, check_settings()).with_hint(
+            "the workspace .spaces/settings.spaces.json is corrupt, run `spaces checkout` again to re-create the workspace",
+        ),
+    ];
+
+    printer.info("doctor", &checks)?;
+
+    let failed: Vec<&str> = checks
+        .iter()
+        .filter(|check| check.is_critical && !check.is_passed)
+        .map(|check| check.name)
+        .collect();
+    if !failed.is_empty() {
+        return Err(format_error!("Critical checks failed: {}", failed.join(", ")));
+    }
+
+    Ok(())
+}
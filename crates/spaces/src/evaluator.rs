@@ -1,4 +1,4 @@
-use crate::{builtins, rules, singleton, workspace};
+use crate::{builtins, executor, rules, singleton, workspace};
 use anyhow::Context;
 use anyhow_source_location::{format_context, format_error};
 use starlark::environment::{FrozenModule, GlobalsBuilder, Module};
@@ -100,6 +100,44 @@ fn evaluate_module(
     Ok(module.freeze()?)
 }
 
+const PRE_RUN_HOOK: &str = "pre-run-hook";
+const POST_RUN_HOOK: &str = "post-run-hook";
+
+fn execute_run_hook(
+    printer: &mut printer::Printer,
+    workspace: workspace::WorkspaceArc,
+    name: &str,
+    hook: Option<executor::exec::Exec>,
+) -> anyhow::Result<()> {
+    let Some(hook) = hook else {
+        return Ok(());
+    };
+    star_logger(printer).message(format!("Running {name}").as_str());
+    let mut multi_progress = printer::MultiProgress::new(printer);
+    let mut progress = multi_progress.add_progress(name, None, None);
+    hook.execute(&mut progress, workspace, name).context(format_context!("{name} failed"))
+}
+
+fn execute_run_rules(
+    printer: &mut printer::Printer,
+    workspace: workspace::WorkspaceArc,
+    phase: rules::Phase,
+) -> anyhow::Result<()> {
+    if singleton::get_repeat() > 1 {
+        rules::execute_repeat(printer, workspace.clone(), phase, singleton::get_repeat())
+            .context(format_context!("Failed to execute repeated runs"))?;
+    } else {
+        let _new_modules = rules::execute(printer, workspace.clone(), phase)
+            .context(format_context!("Failed to execute tasks"))?;
+
+        if let Some(directory) = singleton::get_export_artifacts() {
+            rules::export_artifacts(printer, workspace.clone(), directory.as_ref())
+                .context(format_context!("Failed to export artifacts"))?;
+        }
+    }
+    Ok(())
+}
+
 fn star_logger(printer: &mut printer::Printer) -> logger::Logger {
     logger::Logger::new_printer(printer, "starlark".into())
 }
@@ -109,7 +147,7 @@ pub fn run_starlark_modules(
     workspace: workspace::WorkspaceArc,
     modules: Vec<(Arc<str>, Arc<str>)>,
     phase: rules::Phase,
-    target: Option<Arc<str>>,
+    targets: Vec<Arc<str>>,
 ) -> anyhow::Result<()> {
     star_logger(printer).message("--Run Starlark Modules--");
     let workspace_path = workspace.read().absolute_path.to_owned();
@@ -137,19 +175,39 @@ pub fn run_starlark_modules(
             let mut _workspace_lock = get_state().write();
             singleton::set_active_workspace(workspace.clone());
             star_logger(printer).message(format!("Evaluating module {}", name).as_str());
-            let _ = evaluate_module(
+            let evaluate_result = evaluate_module(
                 workspace_path.clone(),
                 name.clone(),
                 content.to_string(),
                 WithRules::Yes,
             )
-            .context(format_context!("Failed to evaluate module {}", name))?;
+            .context(format_context!("Failed to evaluate module {}", name));
+
+            if singleton::get_is_print_plan() {
+                // the module may load from a repo that is not checked out when printing the plan
+                evaluate_result.context(format_context!(
+                    "--print-plan could not evaluate {name}, modules in repos that are not checked out are not available"
+                ))?;
+            } else {
+                evaluate_result?;
+            }
+        }
+
+        if phase == rules::Phase::Checkout && singleton::get_is_print_plan() {
+            // nothing is cloned or downloaded so modules in checked out repos are not evaluated
+            if module_queue.is_empty() {
+                rules::sort_tasks(&[], phase).context(format_context!("Failed to sort tasks"))?;
+                rules::show_checkout_plan(printer)
+                    .context(format_context!("Failed to show checkout plan"))?;
+                return Ok(());
+            }
+            continue;
         }
 
         // During checkout phase, additional modules may be added to the queue
         // if the repo contains more spaces.star files
         if phase == rules::Phase::Checkout {
-            rules::sort_tasks(None, phase).context(format_context!("Failed to sort tasks"))?;
+            rules::sort_tasks(&[], phase).context(format_context!("Failed to sort tasks"))?;
             star_logger(printer).debug("--Checkout Phase--");
             rules::debug_sorted_tasks(printer, phase)
                 .context(format_context!("Failed to debug sorted tasks"))?;
@@ -198,45 +256,86 @@ pub fn run_starlark_modules(
                 star_logger(printer).info(repro_message.as_str());
             }
 
-            rules::sort_tasks(target.clone(), phase)
+            rules::sort_tasks(&targets, phase)
                 .context(format_context!("Failed to sort tasks"))?;
 
             rules::debug_sorted_tasks(printer, phase)
                 .context(format_context!("Failed to debug sorted tasks"))?;
 
-            let _new_modules = rules::execute(printer, workspace.clone(), phase)
-                .context(format_context!("Failed to execute tasks"))?;
+            if singleton::get_is_dry_run() {
+                rules::dry_run(printer, workspace.clone(), phase)
+                    .context(format_context!("Failed to show the execution plan"))?;
+            } else {
+                let run_result = execute_run_hook(
+                    printer,
+                    workspace.clone(),
+                    PRE_RUN_HOOK,
+                    singleton::get_pre_run_hook(),
+                )
+                .and_then(|_| execute_run_rules(printer, workspace.clone(), phase));
+
+                // the post hook runs even if the run failed (e.g. to stop a service)
+                let post_result = execute_run_hook(
+                    printer,
+                    workspace.clone(),
+                    POST_RUN_HOOK,
+                    singleton::get_post_run_hook(),
+                );
+                run_result?;
+                post_result?;
+            }
         }
         rules::Phase::Evaluate => {
             star_logger(printer).message( "--Evaluate Phase--");
-            rules::sort_tasks(target.clone(), phase)
+            rules::sort_tasks(&targets, phase)
                 .context(format_context!("Failed to sort tasks"))?;
 
             rules::debug_sorted_tasks(printer, rules::Phase::Run)
                 .context(format_context!("Failed to debug sorted tasks"))?;
 
-            rules::show_tasks(printer).context(format_context!("Failed to show tasks"))?;
+            if let Some(show_files) = singleton::get_show_files() {
+                rules::show_files(printer, workspace.clone(), &targets, show_files)
+                    .context(format_context!("Failed to show files"))?;
+            } else if let Some(graph_format) = singleton::get_show_graph() {
+                rules::show_graph(graph_format).context(format_context!("Failed to show graph"))?;
+            } else {
+                rules::show_tasks(printer, singleton::get_list_format())
+                    .context(format_context!("Failed to show tasks"))?;
+            }
+        }
+        rules::Phase::Clean => {
+            star_logger(printer).message("--Clean Phase--");
+            rules::sort_tasks(&targets, rules::Phase::Run)
+                .context(format_context!("Failed to sort tasks"))?;
+
+            rules::clean(printer, workspace.clone())
+                .context(format_context!("Failed to clean rule outputs"))?;
         }
         rules::Phase::Checkout => {
             star_logger(printer).message("--Post Checkout Phase--");
 
             // at this point everything should be preset, sort tasks as if in run phase
-            rules::sort_tasks(target.clone(), rules::Phase::Run)
+            rules::sort_tasks(&targets, rules::Phase::Run)
                 .context(format_context!("Failed to sort tasks"))?;
             rules::debug_sorted_tasks(printer, rules::Phase::PostCheckout)
                 .context(format_context!("Failed to debug sorted tasks"))?;
 
-            rules::execute(printer, workspace.clone(), rules::Phase::PostCheckout)
-                .context(format_context!("failed to execute post checkout phase"))?;
-
             // prepend PATH with sysroot/bin if sysroot/bin is not already in the PATH
-            let mut env = workspace.read().get_env();
+            // before post checkout so checks can run the checked out tools
             let sysroot_bin: Arc<str> =
                 format!("{}/sysroot/bin", workspace.read().absolute_path).into();
-            if !env.paths.contains(&sysroot_bin) {
-                env.paths.insert(0, sysroot_bin);
+            {
+                let mut workspace = workspace.write();
+                if !workspace.env.paths.contains(&sysroot_bin) {
+                    workspace.env.paths.insert(0, sysroot_bin);
+                }
             }
 
+            rules::execute(printer, workspace.clone(), rules::Phase::PostCheckout)
+                .context(format_context!("failed to execute post checkout phase"))?;
+
+            let mut env = workspace.read().get_env();
+
             if workspace.read().is_reproducible() {
                 env.vars.insert(
                     workspace::SPACES_ENV_WORKSPACE_DIGEST.into(),
@@ -0,0 +1,77 @@
+use crate::workspace;
+use anyhow::Context;
+use anyhow_source_location::format_context;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::Arc;
+
+/// Events written as newline-delimited JSON by `spaces run --json-events`
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    TaskStarted {
+        name: Arc<str>,
+    },
+    TaskFinished {
+        name: Arc<str>,
+        status: workspace::RuleStatus,
+        duration_ms: u128,
+        log_file: Arc<str>,
+    },
+    TaskSkipped {
+        name: Arc<str>,
+        reason: Arc<str>,
+    },
+}
+
+#[derive(Debug)]
+enum Sink {
+    Stderr,
+    File(std::fs::File),
+}
+
+#[derive(Debug)]
+struct State {
+    sink: Option<Sink>,
+}
+
+static STATE: state::InitCell<lock::StateLock<State>> = state::InitCell::new();
+
+fn get_state() -> &'static lock::StateLock<State> {
+    if let Some(state) = STATE.try_get() {
+        return state;
+    }
+    STATE.set(lock::StateLock::new(State { sink: None }));
+    STATE.get()
+}
+
+/// `-` writes events to stderr, otherwise events are written to the file at `path`
+pub fn set_output(path: &str) -> anyhow::Result<()> {
+    let sink = if path == "-" {
+        Sink::Stderr
+    } else {
+        let file = std::fs::File::create(path)
+            .context(format_context!("Failed to create json events file {path}"))?;
+        Sink::File(file)
+    };
+    get_state().write().sink = Some(sink);
+    Ok(())
+}
+
+pub fn emit(event: Event) {
+    let mut state = get_state().write();
+    let Some(sink) = state.sink.as_mut() else {
+        return;
+    };
+
+    let Ok(mut line) = serde_json::to_string(&event) else {
+        return;
+    };
+    line.push('\n');
+
+    // events are best effort and must not fail the run
+    let _ = match sink {
+        Sink::Stderr => std::io::stderr().write_all(line.as_bytes()),
+        Sink::File(file) => file.write_all(line.as_bytes()).and_then(|_| file.flush()),
+    };
+}
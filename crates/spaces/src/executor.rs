@@ -9,7 +9,7 @@ pub mod oras;
 
 use crate::workspace;
 use anyhow::Context;
-use anyhow_source_location::format_context;
+use anyhow_source_location::{format_context, format_error};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -41,6 +41,7 @@ pub enum Task {
     CreateArchive(archive::Archive),
     HttpArchive(http_archive::HttpArchive),
     OrasArchive(oras::OrasArchive),
+    OciImage(oras::OciImage),
     AddWhichAsset(asset::AddWhichAsset),
     AddHardLink(asset::AddHardLink),
     AddSoftLink(asset::AddSoftLink),
@@ -59,10 +60,12 @@ impl Task {
         name: &str,
     ) -> anyhow::Result<TaskResult> {
         let mut check_new_modules = false;
+        let mut modules_path = None;
         let mut enabled_targets = Vec::new();
         match self {
             Task::HttpArchive(archive) => archive.execute(progress, workspace.clone(), name),
             Task::OrasArchive(archive) => archive.execute(progress, workspace.clone(), name),
+            Task::OciImage(image) => image.execute(progress, workspace.clone(), name),
             Task::Exec(exec) => exec.execute(&mut progress, workspace.clone(), name),
             Task::ExecIf(exec_if) => {
                 enabled_targets = exec_if.execute(progress, workspace.clone(), name);
@@ -79,6 +82,7 @@ impl Task {
             Task::Capsule(capsule) => capsule.execute(&mut progress, workspace.clone(), name),
             Task::Git(git) => {
                 check_new_modules = git.is_evaluate_spaces_modules && git.working_directory.is_none();
+                modules_path = git.evaluate_spaces_modules_path.clone();
                 git.execute(&mut progress, workspace.clone(), name)
             }
             Task::Target => Ok(()),
@@ -95,22 +99,25 @@ impl Task {
             let parts = name.split(':').collect::<Vec<&str>>();
             if let Some(last) = parts.last() {
                 let workspace_path = std::path::Path::new(workspace.as_ref());
-                let new_repo_path = workspace_path.join(last);
-                // add files in the directory that end in spaces.star
-                let modules = std::fs::read_dir(new_repo_path.clone()).context(format_context!(
-                    "Failed to read workspace directory {new_repo_path:?}"
-                ))?;
+                let relative_modules_path: Arc<str> = match modules_path.as_ref() {
+                    Some(path) => format!("{last}/{}", path.trim_end_matches('/')).into(),
+                    None => (*last).into(),
+                };
+                let new_repo_path = workspace_path.join(relative_modules_path.as_ref());
+                if modules_path.is_some() && !new_repo_path.is_dir() {
+                    return Err(format_error!(
+                        "{name}: evaluate_spaces_modules_path {new_repo_path:?} is not a directory"
+                    ));
+                }
+
+                // add files in the repo (including subdirectories) that end in spaces.star
+                let modules = workspace::find_rules_modules(&new_repo_path.to_string_lossy())
+                    .context(format_context!(
+                        "Failed to find modules in {new_repo_path:?}"
+                    ))?;
 
-                for module in modules.flatten() {
-                    let path = module.path();
-                    if path.is_file() {
-                        let path = path.to_string_lossy().to_string();
-                        if workspace::is_rules_module(path.as_str()) {
-                            let relative_workspace_path =
-                                format!("{}/{}", last, module.file_name().to_string_lossy());
-                            result.new_modules.push(relative_workspace_path.into());
-                        }
-                    }
+                for module in modules {
+                    result.new_modules.push(format!("{relative_modules_path}/{module}").into());
                 }
             }
         }
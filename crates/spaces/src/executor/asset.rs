@@ -1,6 +1,7 @@
 use anyhow::Context;
-use anyhow_source_location::format_context;
+use anyhow_source_location::{format_context, format_error};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 use crate::workspace;
@@ -21,18 +22,8 @@ pub struct UpdateAsset {
     pub destination: Arc<str>,
     pub format: AssetFormat,
     pub value: serde_json::Value,
-}
-
-fn parse_value(format: AssetFormat, content: &str) -> anyhow::Result<serde_json::Value> {
-    match format {
-        AssetFormat::Json => serde_json::from_str(content)
-            .context(format_context!("Failed to parse asset file as JSON",)),
-        AssetFormat::Toml => {
-            toml::from_str(content).context(format_context!("Failed to parse asset file as TOML",))
-        }
-        AssetFormat::Yaml => serde_yaml::from_str(content)
-            .context(format_context!("Failed to parse asset file as YAML",)),
-    }
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_arrays: Option<bool>,
 }
 
 fn format_value(format: AssetFormat, value: &serde_json::Value) -> anyhow::Result<String> {
@@ -53,49 +44,22 @@ impl UpdateAsset {
         workspace: workspace::WorkspaceArc,
         name: &str,
     ) -> anyhow::Result<()> {
-        use json_value_merge::Merge;
-
         let mut logger = logger::Logger::new_progress(&mut progress, name.into());
         let mut workspace_write_lock = workspace.write();
         let workspace_path = workspace_write_lock.get_absolute_path();
 
-        let dest_path = get_destination_path(workspace_path.clone(), &self.destination).context(
-            format_context!(
-                "Failed to get destination path for asset file {}",
-                &self.destination
-            ),
-        )?;
-
         logger.message(format!("update asset {}", self.destination).as_str());
 
-        let new_value = if workspace_write_lock
+        let contributions = workspace_write_lock
             .updated_assets
-            .contains(&self.destination)
-        {
-            logger.debug(format!("load existing value {}", self.destination).as_str());
+            .entry(self.destination.clone())
+            .or_default();
+        contributions.insert(name.into(), self.clone());
 
-            let old_value = std::fs::read_to_string(dest_path.clone()).context(format_context!(
-                "Failed to read asset file {}",
-                dest_path.display()
-            ))?;
-
-            logger
-                .trace(format!("Parsing asset file `{}` as {:?}", old_value, self.format).as_str());
-            let mut old_value = parse_value(self.format, &old_value).context(format_context!(
-                "Failed to parse asset file {}",
-                &self.destination
-            ))?;
-
-            old_value.merge(&self.value);
-
-            old_value
-        } else {
-            logger.debug(format!("Add new value to {}", self.destination).as_str());
-            workspace_write_lock
-                .updated_assets
-                .insert(self.destination.clone());
-            self.value.clone()
-        };
+        let new_value = merge_updates(contributions).context(format_context!(
+            "Failed to merge asset file {}",
+            self.destination
+        ))?;
 
         let content = format_value(self.format, &new_value).context(format_context!(
             "Failed to format asset file {}",
@@ -109,6 +73,95 @@ impl UpdateAsset {
     }
 }
 
+fn join_key_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+// remembers which rule set each value so a conflict can name both rules
+fn record_owners(
+    value: &serde_json::Value,
+    path: &str,
+    rule: &Arc<str>,
+    owners: &mut HashMap<String, Arc<str>>,
+) {
+    if let serde_json::Value::Object(map) = value {
+        for (key, value) in map {
+            record_owners(value, join_key_path(path, key).as_str(), rule, owners);
+        }
+    } else {
+        owners.insert(path.to_string(), rule.clone());
+    }
+}
+
+// merging in rule name order gives the same file no matter which rule runs first
+fn merge_updates(updates: &BTreeMap<Arc<str>, UpdateAsset>) -> anyhow::Result<serde_json::Value> {
+    let mut new_value = serde_json::Value::Null;
+    let mut owners = HashMap::new();
+    for (rule, update) in updates.iter() {
+        let is_merge_arrays = update.merge_arrays.unwrap_or(false);
+        merge_value(&mut new_value, &update.value, "", rule, is_merge_arrays, &mut owners)
+            .context(format_context!("Failed to merge {rule}"))?;
+    }
+    Ok(new_value)
+}
+
+/// Deep merges `source` into `target`. Objects are merged key by key. With
+/// `is_merge_arrays`, the items of a source array are appended to the target array.
+/// Any other value must be equal in every rule that sets it.
+fn merge_value(
+    target: &mut serde_json::Value,
+    source: &serde_json::Value,
+    path: &str,
+    rule: &Arc<str>,
+    is_merge_arrays: bool,
+    owners: &mut HashMap<String, Arc<str>>,
+) -> anyhow::Result<()> {
+    match (target, source) {
+        (serde_json::Value::Array(target_items), serde_json::Value::Array(source_items))
+            if is_merge_arrays =>
+        {
+            target_items.extend(source_items.iter().cloned());
+        }
+        (serde_json::Value::Object(target_map), serde_json::Value::Object(source_map)) => {
+            for (key, source_value) in source_map {
+                let key_path = join_key_path(path, key);
+                if let Some(target_value) = target_map.get_mut(key) {
+                    merge_value(
+                        target_value,
+                        source_value,
+                        key_path.as_str(),
+                        rule,
+                        is_merge_arrays,
+                        owners,
+                    )?;
+                } else {
+                    record_owners(source_value, key_path.as_str(), rule, owners);
+                    target_map.insert(key.clone(), source_value.clone());
+                }
+            }
+        }
+        (target, source) => {
+            if target.is_null() {
+                record_owners(source, path, rule, owners);
+                *target = source.clone();
+            } else if target != source {
+                let owner = owners
+                    .get(path)
+                    .map(|owner| owner.to_string())
+                    .unwrap_or_else(|| "another rule".to_string());
+                return Err(format_error!(
+                    "`{path}` is set to {target} by {owner} and to {source} by {rule}"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct AddWhichAsset {
@@ -151,20 +204,36 @@ impl AddWhichAsset {
 pub struct AddHardLink {
     pub source: String,
     pub destination: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// Expected sha256 of the source, checked before the link is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<Arc<str>>,
 }
 
 impl AddHardLink {
     pub fn execute(
         &self,
-        _progress: printer::MultiProgressBar,
+        mut progress: printer::MultiProgressBar,
         workspace: workspace::WorkspaceArc,
-        _name: &str,
+        name: &str,
     ) -> anyhow::Result<()> {
         // create the hard link to sysroot
         let workspace = workspace.read().get_absolute_path();
         let destination = format!("{}/{}", workspace, self.destination);
         let source = self.source.clone();
 
+        if let Some(sha256) = self.sha256.as_ref() {
+            let contents =
+                std::fs::read(source.as_str()).context(format_context!("Failed to read {source}"))?;
+            let actual_sha256 = sha256::digest(contents);
+            if actual_sha256 != sha256.as_ref() {
+                return Err(format_error!(
+                    "sha256 mismatch for {source}: expected {sha256}, got {actual_sha256}"
+                ));
+            }
+        }
+
         http_archive::HttpArchive::create_hard_link(destination.clone(), source.clone()).context(
             format_context!(
                 "Failed to create hard link from {} to {}",
@@ -173,6 +242,11 @@ impl AddHardLink {
             ),
         )?;
 
+        if let Some(mode) = self.mode.as_ref() {
+            set_file_mode(&mut progress, name, destination.as_str(), mode)
+                .context(format_context!("Failed to set mode for {destination}"))?;
+        }
+
         Ok(())
     }
 }
@@ -182,23 +256,108 @@ impl AddHardLink {
 pub struct AddAsset {
     pub destination: String,
     pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub substitutions: Option<HashMap<String, String>>,
+}
+
+/// Replaces `{{key}}` tokens in `content`. `\{{` is written as a literal `{{`.
+fn substitute(content: &str, values: &HashMap<String, String>) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(index) = rest.find("{{") {
+        if rest[..index].ends_with('\\') {
+            result.push_str(&rest[..index - 1]);
+            result.push_str("{{");
+            rest = &rest[index + 2..];
+            continue;
+        }
+        result.push_str(&rest[..index]);
+        let after_open = &rest[index + 2..];
+        let end = after_open.find("}}").ok_or(format_error!(
+            "Missing `}}}}` after `{{{{` in asset content"
+        ))?;
+        let key = after_open[..end].trim();
+        let value = values.get(key).ok_or(format_error!(
+            "No substitution for `{{{{{key}}}}}` in asset content"
+        ))?;
+        result.push_str(value);
+        rest = &after_open[end + 2..];
+    }
+    result.push_str(rest);
+    Ok(result)
 }
 
 impl AddAsset {
     pub fn execute(
         &self,
-        _progress: printer::MultiProgressBar,
+        mut progress: printer::MultiProgressBar,
         workspace: workspace::WorkspaceArc,
-        _name: &str,
+        name: &str,
     ) -> anyhow::Result<()> {
         let workspace_write_lock = workspace.write();
         let workspace_path = workspace_write_lock.get_absolute_path();
-        save_asset(workspace_path, &self.destination, &self.content)
+
+        // tokens are resolved here so the rule digest does not depend on the workspace location
+        let content = if let Some(substitutions) = self.substitutions.as_ref() {
+            let mut values = substitutions.clone();
+            values.insert("workspace".to_string(), workspace_path.to_string());
+            values.insert("rule".to_string(), name.to_string());
+            substitute(&self.content, &values).context(format_context!(
+                "Failed to substitute values in {}",
+                self.destination
+            ))?
+        } else {
+            self.content.clone()
+        };
+
+        save_asset(workspace_path.clone(), &self.destination, &content)
             .context(format_context!("failed to add asset"))?;
+
+        if let Some(mode) = self.mode.as_ref() {
+            let destination = format!("{workspace_path}/{}", self.destination);
+            set_file_mode(&mut progress, name, destination.as_str(), mode)
+                .context(format_context!("Failed to set mode for {destination}"))?;
+        }
         Ok(())
     }
 }
 
+/// Parses an octal file mode such as `"0755"` or `"755"`
+pub fn parse_mode(mode: &str) -> anyhow::Result<u32> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    let value = u32::from_str_radix(digits, 8)
+        .context(format_context!("mode `{mode}` must be an octal string like \"0755\""))?;
+    if value > 0o7777 {
+        return Err(format_error!("mode `{mode}` is larger than 07777"));
+    }
+    Ok(value)
+}
+
+fn set_file_mode(
+    progress: &mut printer::MultiProgressBar,
+    name: &str,
+    path: &str,
+    mode: &str,
+) -> anyhow::Result<()> {
+    let mode = parse_mode(mode).context(format_context!("bad mode for {path}"))?;
+    let mut logger = logger::Logger::new_progress(progress, name.into());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        logger.debug(format!("set mode {mode:o} on {path}").as_str());
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .context(format_context!("Failed to set permissions {mode:o} on {path}"))?;
+    }
+
+    #[cfg(windows)]
+    logger.warning(format!("mode {mode:o} for {path} is ignored on windows").as_str());
+
+    Ok(())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct AddSoftLink {
@@ -292,3 +451,70 @@ fn save_asset(workspace_path: Arc<str>, destination: &str, content: &str) -> any
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vscode_task(label: &str, merge_arrays: Option<bool>) -> UpdateAsset {
+        UpdateAsset {
+            destination: ".vscode/tasks.json".into(),
+            format: AssetFormat::Json,
+            value: serde_json::json!({
+                "version": "2.0.0",
+                "tasks": [{"label": label, "type": "cargo"}],
+            }),
+            merge_arrays,
+        }
+    }
+
+    #[test]
+    fn merge_arrays_appends_tasks_in_rule_order() {
+        let mut updates = BTreeMap::new();
+        // inserted out of order, rule names decide the order
+        updates.insert("//:test_task".into(), vscode_task("test", Some(true)));
+        updates.insert("//:build_task".into(), vscode_task("build", Some(true)));
+
+        let value = merge_updates(&updates).unwrap();
+        assert_eq!(value["version"], "2.0.0");
+        let labels: Vec<&str> = value["tasks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|task| task["label"].as_str().unwrap())
+            .collect();
+        assert_eq!(labels, vec!["build", "test"]);
+    }
+
+    #[test]
+    fn conflicting_arrays_name_the_key_path() {
+        let mut updates = BTreeMap::new();
+        updates.insert("//:build_task".into(), vscode_task("build", None));
+        updates.insert("//:test_task".into(), vscode_task("test", None));
+
+        let error = format!("{:#}", merge_updates(&updates).unwrap_err());
+        assert!(error.contains("`tasks`"), "{error}");
+        assert!(error.contains("//:build_task"), "{error}");
+    }
+
+    #[test]
+    fn substitute_tokens_and_escapes() {
+        let values: HashMap<String, String> = [
+            ("workspace".to_string(), "/ws".to_string()),
+            ("rule".to_string(), "//:config".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        let content = "root={{workspace}} rule={{ rule }} literal=\\{{workspace}} end";
+        assert_eq!(
+            substitute(content, &values).unwrap(),
+            "root=/ws rule=//:config literal={{workspace}} end"
+        );
+        assert_eq!(substitute("no tokens", &values).unwrap(), "no tokens");
+
+        let error = format!("{:#}", substitute("{{missing}}", &values).unwrap_err());
+        assert!(error.contains("{{missing}}"), "{error}");
+        assert!(substitute("{{workspace", &values).is_err());
+    }
+}
@@ -319,6 +319,8 @@ impl Capsule {
             env: Some(env),
             redirect_stdout: None,
             expect: None,
+            env_files: None,
+            env_mode: None,
         };
 
         let checkout_name = format!("{}_checkout", capsule_run_info.get_workspace_name());
@@ -356,6 +358,8 @@ impl Capsule {
             ),
             redirect_stdout: None,
             expect: None,
+            env_files: None,
+            env_mode: None,
         };
 
         let run_name = format!("{}_run", capsule_run_info.get_workspace_name());
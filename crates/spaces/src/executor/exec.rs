@@ -3,6 +3,7 @@ use anyhow::Context;
 use anyhow_source_location::{format_context, format_error};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 #[derive(Debug, Clone, Default)]
@@ -39,12 +40,29 @@ fn get_process_id(rule: &str) -> Option<u32> {
     state.processes.get(rule).copied()
 }
 
+/// Automatic variable with the absolute path to the workspace
+pub const WORKSPACE_ENV_VAR: &str = "WORKSPACE";
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Expect {
     Failure,
     Success,
     Any,
 }
+/// How the `env` of an exec rule combines with the workspace environment
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum EnvMode {
+    /// Start with the workspace environment and override it with the rule's env
+    #[default]
+    Extend,
+    /// Start with PATH, HOME (if the workspace inherits it) and WORKSPACE and add the rule's env
+    Replace,
+}
+
+impl EnvMode {
+    const ESSENTIAL_VARS: [&'static str; 2] = ["PATH", "HOME"];
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Exec {
@@ -54,9 +72,110 @@ pub struct Exec {
     pub working_directory: Option<Arc<str>>,
     pub redirect_stdout: Option<Arc<str>>,
     pub expect: Option<Expect>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_files: Option<Vec<Arc<str>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_mode: Option<EnvMode>,
+}
+
+// sanitized rule names can collide, the counter keeps the directories unique
+static ISOLATED_TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// removes the per-rule temporary directory when the rule completes unless it is kept
+struct IsolatedTmp {
+    path: std::path::PathBuf,
+    is_keep: bool,
+}
+
+impl IsolatedTmp {
+    // TMPDIR is used on macOS and Linux, TMP and TEMP are used on Windows
+    const ENV_VARS: [&'static str; 3] = ["TMPDIR", "TMP", "TEMP"];
+
+    fn new(name: &str) -> anyhow::Result<Self> {
+        let sanitized_name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        let count = ISOLATED_TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!(
+            "spaces-{}-{sanitized_name}-{count}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&path)
+            .context(format_context!("Failed to create temporary directory {path:?}"))?;
+        Ok(Self {
+            path,
+            is_keep: false,
+        })
+    }
+}
+
+impl Drop for IsolatedTmp {
+    fn drop(&mut self) {
+        if !self.is_keep {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+fn load_env_file(path: &str) -> anyhow::Result<Vec<(Arc<str>, Arc<str>)>> {
+    let content =
+        std::fs::read_to_string(path).context(format_context!("Failed to read env file {path}"))?;
+    environment::parse_dotenv(content.as_str()).context(format_context!("Failed to parse {path}"))
+}
+
+fn get_secret_values(
+    environment_map: &HashMap<Arc<str>, Arc<str>>,
+    secret_vars: Option<&Vec<Arc<str>>>,
+) -> Vec<Arc<str>> {
+    secret_vars
+        .into_iter()
+        .flatten()
+        .filter_map(|secret_var| environment_map.get(secret_var).cloned())
+        .collect()
 }
 
 impl Exec {
+    /// Precedence is workspace env < isolated temporary directory < env_files < env < WORKSPACE
+    fn get_environment_map(
+        &self,
+        mut environment_map: HashMap<Arc<str>, Arc<str>>,
+        workspace_path: &str,
+        isolated_tmp: Option<&IsolatedTmp>,
+    ) -> anyhow::Result<HashMap<Arc<str>, Arc<str>>> {
+        if self.env_mode.unwrap_or_default() == EnvMode::Replace {
+            environment_map.retain(|key, _| EnvMode::ESSENTIAL_VARS.contains(&key.as_ref()));
+        }
+
+        // replaces the workspace values, the rule's env_files and env can still set them
+        if let Some(isolated_tmp) = isolated_tmp {
+            let tmp_path: Arc<str> = isolated_tmp.path.to_string_lossy().into();
+            for var in IsolatedTmp::ENV_VARS {
+                environment_map.insert(var.into(), tmp_path.clone());
+            }
+        }
+
+        for env_file in self.env_files.clone().unwrap_or_default() {
+            let env_file_path = if std::path::Path::new(env_file.as_ref()).is_absolute() {
+                env_file.to_string()
+            } else {
+                format!("{workspace_path}/{env_file}")
+            };
+            let vars = load_env_file(env_file_path.as_str())
+                .context(format_context!("Failed to load env file {env_file}"))?;
+            environment_map.extend(vars);
+        }
+
+        for (key, value) in self.env.clone().unwrap_or_default() {
+            environment_map.insert(key, value);
+        }
+
+        // set at run time so the rule digest stays the same in every workspace location
+        environment_map.insert(WORKSPACE_ENV_VAR.into(), workspace_path.into());
+
+        Ok(environment_map)
+    }
+
     pub fn execute(
         &self,
         progress: &mut printer::MultiProgressBar,
@@ -66,15 +185,30 @@ impl Exec {
         let arguments = self.args.clone().unwrap_or_default();
         let workspace_env = workspace.read().get_env();
 
-        let mut environment_map = workspace_env
+        let workspace_vars = workspace_env
             .get_vars()
             .context(format_context!("Failed to get env vars"))?;
 
-        for (key, value) in self.env.clone().unwrap_or_default() {
-            environment_map.insert(key, value);
+        let workspace_path = workspace.read().get_absolute_path();
+
+        let isolated_tmp = if singleton::get_is_isolate_tmp() {
+            Some(
+                IsolatedTmp::new(name)
+                    .context(format_context!("Failed to isolate the temporary directory"))?,
+            )
+        } else {
+            None
+        };
+
+        let environment_map = self
+            .get_environment_map(workspace_vars, workspace_path.as_ref(), isolated_tmp.as_ref())
+            .context(format_context!("Failed to load env files for {name}"))?;
+
+        // env_files and env can also set secret values, the workspace values are already registered
+        for secret in get_secret_values(&environment_map, workspace_env.secret_vars.as_ref()) {
+            logger::add_secret(secret.as_ref());
         }
 
-        let workspace_path = workspace.read().get_absolute_path();
         let environment = environment_map.into_iter().collect::<Vec<_>>();
 
         let log_file_path = if singleton::get_is_ci() {
@@ -116,6 +250,24 @@ impl Exec {
         let result = progress.execute_process(&self.command, options);
 
         handle_process_ended(name);
+        if let Some(mut isolated_tmp) = isolated_tmp {
+            let is_failed = match (&result, self.expect) {
+                (Ok(_), Some(Expect::Failure)) => true,
+                (Ok(_), _) => false,
+                (Err(_), Some(Expect::Failure | Expect::Any)) => false,
+                (Err(_), _) => true,
+            };
+            if is_failed && singleton::get_is_keep_tmp_on_failure() {
+                isolated_tmp.is_keep = true;
+                logger(progress, name).warning(
+                    format!(
+                        "keeping temporary directory for {name}: {:?}",
+                        isolated_tmp.path
+                    )
+                    .as_str(),
+                );
+            }
+        }
 
         logger(progress, name).message(format!("log file for {name}: {log_file_path:?}").as_str());
 
@@ -294,3 +446,128 @@ impl ExecIf {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_vars(vars: &[(&str, &str)]) -> HashMap<Arc<str>, Arc<str>> {
+        vars.iter().map(|(key, value)| ((*key).into(), (*value).into())).collect()
+    }
+
+    #[test]
+    fn environment_precedence() {
+        let root = std::env::temp_dir().join(format!("spaces-env-files-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("local.env"), "FROM_FILE=file\nOVERRIDE=file\n").unwrap();
+        let absolute_env_file = root.join("absolute.env");
+        std::fs::write(&absolute_env_file, "export ABSOLUTE=\"yes\"\nWORKSPACE=file\n").unwrap();
+        let workspace_path = root.to_string_lossy().to_string();
+
+        let exec = Exec {
+            command: "true".into(),
+            args: None,
+            env: Some(to_vars(&[("OVERRIDE", "env"), ("WORKSPACE", "env")])),
+            working_directory: None,
+            redirect_stdout: None,
+            expect: None,
+            env_files: Some(vec![
+                "local.env".into(),
+                absolute_env_file.to_string_lossy().into(),
+            ]),
+            env_mode: None,
+        };
+
+        let workspace_vars = to_vars(&[("FROM_FILE", "workspace"), ("PATH", "/bin")]);
+        let environment_map = exec
+            .get_environment_map(workspace_vars, workspace_path.as_str(), None)
+            .unwrap();
+
+        assert_eq!(environment_map["PATH"].as_ref(), "/bin");
+        assert_eq!(environment_map["FROM_FILE"].as_ref(), "file");
+        assert_eq!(environment_map["ABSOLUTE"].as_ref(), "yes");
+        assert_eq!(environment_map["OVERRIDE"].as_ref(), "env");
+        assert_eq!(environment_map["WORKSPACE"].as_ref(), workspace_path.as_str());
+
+        let missing = Exec {
+            env_files: Some(vec!["missing.env".into()]),
+            ..exec
+        };
+        assert!(missing
+            .get_environment_map(HashMap::new(), workspace_path.as_str(), None)
+            .is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn replace_keeps_essentials_and_secrets() {
+        let exec = Exec {
+            command: "true".into(),
+            args: None,
+            env: Some(to_vars(&[("API_KEY", "rule-secret")])),
+            working_directory: None,
+            redirect_stdout: None,
+            expect: None,
+            env_files: None,
+            env_mode: Some(EnvMode::Replace),
+        };
+
+        let workspace_vars = to_vars(&[
+            ("PATH", "/bin"),
+            ("HOME", "/home/user"),
+            ("TOKEN", "workspace-secret"),
+            ("OTHER", "other"),
+        ]);
+        let environment_map = exec
+            .get_environment_map(workspace_vars, "/workspace", None)
+            .unwrap();
+
+        let mut keys: Vec<&str> = environment_map.keys().map(|key| key.as_ref()).collect();
+        keys.sort();
+        assert_eq!(keys, vec!["API_KEY", "HOME", "PATH", "WORKSPACE"]);
+        assert_eq!(environment_map["WORKSPACE"].as_ref(), "/workspace");
+
+        // TOKEN is registered with the workspace env, rule values are registered by the exec
+        let secret_vars: Vec<Arc<str>> = vec!["TOKEN".into(), "API_KEY".into()];
+        let secrets = get_secret_values(&environment_map, Some(&secret_vars));
+        assert_eq!(secrets, vec![Arc::<str>::from("rule-secret")]);
+    }
+
+    #[test]
+    fn isolated_tmp_does_not_replace_the_rule_env() {
+        let exec = Exec {
+            command: "true".into(),
+            args: None,
+            env: Some(to_vars(&[("TMP", "rule")])),
+            working_directory: None,
+            redirect_stdout: None,
+            expect: None,
+            env_files: None,
+            env_mode: None,
+        };
+
+        let isolated_tmp = IsolatedTmp::new("//:isolated").unwrap();
+        let tmp_path = isolated_tmp.path.to_string_lossy().to_string();
+        let workspace_vars = to_vars(&[("TMPDIR", "/tmp"), ("TEMP", "/tmp")]);
+        let environment_map = exec
+            .get_environment_map(workspace_vars, "/workspace", Some(&isolated_tmp))
+            .unwrap();
+
+        assert_eq!(environment_map["TMPDIR"].as_ref(), tmp_path.as_str());
+        assert_eq!(environment_map["TEMP"].as_ref(), tmp_path.as_str());
+        assert_eq!(environment_map["TMP"].as_ref(), "rule");
+    }
+
+    #[test]
+    fn isolated_tmp_directories_are_unique() {
+        let first = IsolatedTmp::new("//:a/b").unwrap();
+        let second = IsolatedTmp::new("//:a_b").unwrap();
+        assert_ne!(first.path, second.path);
+
+        let first_path = first.path.clone();
+        drop(first);
+        assert!(!first_path.exists());
+        assert!(second.path.exists());
+    }
+}
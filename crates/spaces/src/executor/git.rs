@@ -1,4 +1,4 @@
-use crate::workspace;
+use crate::{singleton, workspace};
 use anyhow::Context;
 use anyhow_source_location::{format_context, format_error};
 use serde::{Deserialize, Serialize};
@@ -17,11 +17,128 @@ pub struct Git {
     pub checkout: git::Checkout,
     pub clone: git::Clone,
     pub is_evaluate_spaces_modules: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evaluate_spaces_modules_path: Option<Arc<str>>,
     pub sparse_checkout: Option<git::SparseCheckout>,
     pub working_directory: Option<Arc<str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patches: Option<Vec<Patch>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dirty_policy: Option<git::DirtyPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shallow_since: Option<Arc<str>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Patch {
+    pub path: Arc<str>,
+    // the content digest is part of the task so editing a patch changes the rule digest
+    pub digest: Arc<str>,
+}
+
+/// A revision made of 7 to 40 hex digits is treated as a commit
+fn is_commit_revision(revision: &str) -> bool {
+    (7..=40).contains(&revision.len()) && revision.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Compares a locked revision with the `(commit, ref)` pairs the remote lists
+/// for `revision`. The lock has the tag for tags and the commit for branches.
+fn get_lock_drift(
+    name: &str,
+    revision: &str,
+    locked_rev: &str,
+    remote_refs: &[(Arc<str>, Arc<str>)],
+) -> Option<String> {
+    if is_commit_revision(revision) {
+        // a commit doesn't move, the lock may still name its tag
+        let is_same = !is_commit_revision(locked_rev)
+            || locked_rev.starts_with(revision)
+            || revision.starts_with(locked_rev);
+        if is_same {
+            return None;
+        }
+        return Some(format!(
+            "  {name}: locked at {locked_rev}, the rule checks out {revision}"
+        ));
+    }
+
+    let branch_ref = format!("refs/heads/{revision}");
+    if let Some((commit, _)) = remote_refs
+        .iter()
+        .find(|(_, ref_name)| ref_name.as_ref() == branch_ref)
+    {
+        if commit.as_ref() == locked_rev {
+            return None;
+        }
+        return Some(format!(
+            "  {name}: locked at {locked_rev}, {revision} resolves to {commit}"
+        ));
+    }
+
+    let tag_ref = format!("refs/tags/{revision}");
+    let tag_commits: Vec<&str> = remote_refs
+        .iter()
+        .filter(|(_, ref_name)| ref_name.trim_end_matches("^{}") == tag_ref)
+        .map(|(commit, _)| commit.as_ref())
+        .collect();
+    if tag_commits.is_empty() {
+        return Some(format!("  {name}: {revision} is not on the remote"));
+    }
+    let is_same = locked_rev == revision || tag_commits.contains(&locked_rev);
+    if is_same {
+        return None;
+    }
+    Some(format!(
+        "  {name}: locked at {locked_rev}, the rule checks out {revision}"
+    ))
 }
 
 impl Git {
+    /// `--depth` and `--shallow-since` arguments for clone and fetch
+    fn get_history_arguments(&self) -> Vec<Arc<str>> {
+        let mut arguments: Vec<Arc<str>> = Vec::new();
+        if let Some(depth) = self.depth {
+            arguments.push(format!("--depth={depth}").into());
+        }
+        if let Some(shallow_since) = self.shallow_since.as_ref() {
+            arguments.push(format!("--shallow-since={shallow_since}").into());
+        }
+        arguments
+    }
+
+    /// A limited history clone only has the default branch, so branch and tag
+    /// revisions are cloned with `--branch`
+    fn get_history_clone_arguments(&self) -> Vec<Arc<str>> {
+        let mut arguments = self.get_history_arguments();
+        if arguments.is_empty() {
+            return arguments;
+        }
+        if let git::Checkout::Revision(revision) = &self.checkout {
+            if !is_commit_revision(revision) {
+                // "branch:semver" is resolved from the tags on the branch
+                let branch = revision.split(':').next().unwrap_or(revision);
+                arguments.push("--branch".into());
+                arguments.push(branch.into());
+            }
+        }
+        arguments
+    }
+
+    /// The commit that must be fetched after a limited history clone
+    fn get_history_fetch_commit(&self) -> Option<Arc<str>> {
+        if self.get_history_arguments().is_empty() {
+            return None;
+        }
+        match &self.checkout {
+            git::Checkout::Revision(revision) if is_commit_revision(revision) => {
+                Some(revision.clone())
+            }
+            _ => None,
+        }
+    }
 
     fn get_clone_working_directory(&self, workspace: workspace::WorkspaceArc) -> Arc<str> {
         if let Some(directory) = self.working_directory.as_ref() {
@@ -107,6 +224,18 @@ impl Git {
         if self.sparse_checkout.is_some() {
             clone_arguments.push("--no-checkout".into());
         }
+        clone_arguments.extend(self.get_history_clone_arguments());
+
+        // borrow objects from the store if a worktree clone already fetched this url
+        let store_path = workspace.read().get_store_path();
+        if let Some(reference) =
+            git::BareRepository::get_store_reference(store_path.as_ref(), &self.url)
+        {
+            git::BareRepository::protect_reference(progress, &self.url, reference.clone())
+                .context(format_context!("{name} - Failed to use store reference"))?;
+            clone_arguments.push("--reference-if-able".into());
+            clone_arguments.push(reference);
+        }
 
         clone_arguments.push(self.url.clone());
         clone_arguments.push(self.spaces_key.clone());
@@ -135,6 +264,19 @@ impl Git {
                 ))?;
         }
 
+        if let Some(commit) = self.get_history_fetch_commit() {
+            // the commit may be outside of the limited history of the default branch
+            let arguments = [
+                vec!["fetch".into()],
+                self.get_history_arguments(),
+                vec!["origin".into(), commit.clone()],
+            ]
+            .concat();
+            repository
+                .execute(progress, arguments)
+                .context(format_context!("{name} - Failed to fetch {commit}"))?;
+        }
+
         repository
             .checkout(progress, &self.checkout)
             .context(format_context!(
@@ -162,17 +304,24 @@ impl Git {
 
         let workspace_directory = self.get_clone_working_directory(workspace.clone());
 
+        // depth and shallow_since replace the default depth of 1
+        let mut arguments: Vec<Arc<str>> = vec!["clone".into()];
+        let history_arguments = self.get_history_arguments();
+        if history_arguments.is_empty() {
+            arguments.extend(["--depth".into(), "1".into()]);
+        } else {
+            arguments.extend(history_arguments);
+        }
+        arguments.extend([
+            self.url.clone(),
+            self.spaces_key.clone(),
+            "--branch".into(),
+            branch.clone(),
+            "--single-branch".into(),
+        ]);
+
         let clone_options = printer::ExecuteOptions {
-            arguments: vec![
-                "clone".into(),
-                "--depth".into(),
-                "1".into(),
-                self.url.clone(),
-                self.spaces_key.clone(),
-                "--branch".into(),
-                branch.clone(),
-                "--single-branch".into(),
-            ],
+            arguments,
             working_directory: Some(workspace_directory),
             ..Default::default()
         };
@@ -182,6 +331,8 @@ impl Git {
             logger(progress, self.url.clone())
                 .message(format!("{} already exists", self.spaces_key).as_str());
         } else {
+            git::check_offline(&self.url, &clone_options.arguments)
+                .context(format_context!("{name} - Failed to clone {}", self.spaces_key))?;
             logger(progress, self.url.clone())
                 .trace(format!("git clone {clone_options:?}").as_str());
 
@@ -196,12 +347,88 @@ impl Git {
         Ok(())
     }
 
-    pub fn execute(
+    fn apply_patches(
+        &self,
+        progress: &mut printer::MultiProgressBar,
+        working_directory: Arc<str>,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        for patch in self.patches.iter().flatten() {
+            // a repo that already exists is not cloned again so the patch may already be applied
+            let reverse_check_options = printer::ExecuteOptions {
+                working_directory: Some(working_directory.clone()),
+                arguments: vec![
+                    "apply".into(),
+                    "--reverse".into(),
+                    "--check".into(),
+                    patch.path.clone(),
+                ],
+                ..Default::default()
+            };
+            if git::execute_git_command(progress, &self.url, reverse_check_options).is_ok() {
+                logger(progress, self.url.clone())
+                    .message(format!("{} is already applied", patch.path).as_str());
+                continue;
+            }
+
+            let check_options = printer::ExecuteOptions {
+                working_directory: Some(working_directory.clone()),
+                arguments: vec!["apply".into(), "--check".into(), patch.path.clone()],
+                ..Default::default()
+            };
+            git::execute_git_command(progress, &self.url, check_options).context(
+                format_context!(
+                    "{name} - patch {} does not apply to {}",
+                    patch.path,
+                    self.spaces_key
+                ),
+            )?;
+
+            let apply_options = printer::ExecuteOptions {
+                working_directory: Some(working_directory.clone()),
+                arguments: vec!["apply".into(), patch.path.clone()],
+                ..Default::default()
+            };
+            git::execute_git_command(progress, &self.url, apply_options).context(
+                format_context!(
+                    "{name} - Failed to apply patch {} to {}",
+                    patch.path,
+                    self.spaces_key
+                ),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn execute_checkout(
         &self,
         progress: &mut printer::MultiProgressBar,
         workspace: workspace::WorkspaceArc,
         name: &str,
     ) -> anyhow::Result<()> {
+        let is_history_limited = self.depth.is_some() || self.shallow_since.is_some();
+        if is_history_limited {
+            if matches!(self.clone, git::Clone::Worktree) {
+                logger(progress, self.url.clone()).warning(
+                    format!(
+                        "{name}: depth and shallow_since are ignored for Worktree clones, the store repository is shared"
+                    )
+                    .as_str(),
+                );
+            }
+            if let git::Checkout::Revision(revision) = &self.checkout {
+                if revision.contains(':') {
+                    logger(progress, self.url.clone()).warning(
+                        format!(
+                            "{name}: {revision} is resolved from the branch log, which only has the limited history"
+                        )
+                        .as_str(),
+                    );
+                }
+            }
+        }
+
         match self.clone {
             git::Clone::Worktree => self
                 .execute_worktree_clone(progress, workspace.clone(), name)
@@ -230,21 +457,31 @@ impl Git {
         let mut is_locked = false;
         let working_directory = self.get_working_directory_in_repo(workspace.clone());
         if workspace.read().is_create_lock_file {
-            if let Some(commit_hash) =
-                git::get_commit_hash(progress, &self.url, working_directory.as_ref()).context(
-                    format_context!("Failed to get commit hash for {working_directory}"),
-                )?
+            if let Some(rev) = self
+                .get_lock_rev(progress, working_directory.as_ref(), ref_name.as_ref())
+                .context(format_context!("Failed to resolve {}", self.spaces_key))?
             {
-                let rev: Arc<str> =
-                    if let Some(tag) = git::get_commit_tag(progress, &self.url, &self.spaces_key) {
-                        tag
-                    } else {
-                        commit_hash
-                    };
-                // strip the trailing newline
                 workspace.write().add_git_commit_lock(name, rev);
             }
         } else if let Some(commit_hash) = workspace.read().locks.get(name) {
+            let history_arguments = self.get_history_arguments();
+            if !history_arguments.is_empty() && !matches!(self.clone, git::Clone::Worktree) {
+                // the locked commit may be outside of the limited history
+                let fetch_options = printer::ExecuteOptions {
+                    working_directory: Some(working_directory.clone()),
+                    arguments: [
+                        vec!["fetch".into()],
+                        history_arguments,
+                        vec!["origin".into(), commit_hash.clone()],
+                    ]
+                    .concat(),
+                    ..Default::default()
+                };
+                git::execute_git_command(progress, &self.url, fetch_options).context(
+                    format_context!("Failed to fetch locked commit for {}", self.spaces_key),
+                )?;
+            }
+
             let options = printer::ExecuteOptions {
                 working_directory: Some(working_directory.clone()),
                 arguments: vec!["checkout".into(), "--detach".into(), commit_hash.clone()],
@@ -280,4 +517,248 @@ impl Git {
 
         Ok(())
     }
+
+    /// The revision the lock file records for the checked out repo: the tag or
+    /// commit, branches are always pinned to the commit because they move
+    fn get_lock_rev(
+        &self,
+        progress: &mut printer::MultiProgressBar,
+        working_directory: &str,
+        ref_name: &str,
+    ) -> anyhow::Result<Option<Arc<str>>> {
+        let Some(commit_hash) = git::get_commit_hash(progress, &self.url, working_directory)
+            .context(format_context!(
+                "Failed to get commit hash for {working_directory}"
+            ))?
+        else {
+            return Ok(None);
+        };
+        let is_branch = matches!(self.checkout, git::Checkout::NewBranch(_))
+            || git::is_branch(progress, &self.url, working_directory, ref_name);
+        let tag = if is_branch {
+            None
+        } else {
+            git::get_commit_tag(progress, &self.url, working_directory)
+        };
+        Ok(Some(tag.unwrap_or(commit_hash)))
+    }
+
+    /// Compares the lock with what the rule resolves to on the remote, before
+    /// anything is cloned. Drift is collected so every difference is reported.
+    fn check_frozen_lock(
+        &self,
+        progress: &mut printer::MultiProgressBar,
+        workspace: workspace::WorkspaceArc,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        let locked_rev = workspace.read().locks.get(name).cloned();
+        let drift = match (locked_rev, &self.checkout) {
+            (None, _) => Some(format!("  {name}: not in the lock file")),
+            // semver revisions are resolved from the branch history after the clone
+            (Some(locked_rev), git::Checkout::Revision(revision)) if !revision.contains(':') => {
+                let remote_refs = if is_commit_revision(revision) {
+                    Vec::new()
+                } else {
+                    git::get_remote_refs(progress, &self.url, revision)
+                        .context(format_context!("Failed to resolve {revision}"))?
+                };
+                get_lock_drift(name, revision, &locked_rev, &remote_refs)
+            }
+            _ => None,
+        };
+
+        if let Some(drift) = drift {
+            logger(progress, self.url.clone()).warning(drift.trim_start());
+            workspace.write().lock_drift.push(drift.into());
+        }
+        Ok(())
+    }
+
+    pub fn execute(
+        &self,
+        progress: &mut printer::MultiProgressBar,
+        workspace: workspace::WorkspaceArc,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        git::set_log_rule_name(Some(name.into()));
+        let result = self.execute_rule(progress, workspace, name);
+        // the thread may run other rules, their git logs are not named after this one
+        git::set_log_rule_name(None);
+        result
+    }
+
+    fn execute_rule(
+        &self,
+        progress: &mut printer::MultiProgressBar,
+        workspace: workspace::WorkspaceArc,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        if singleton::get_is_frozen() {
+            // nothing is cloned or checked out once the lock has drifted
+            self.check_frozen_lock(progress, workspace.clone(), name)
+                .context(format_context!(
+                    "Failed to check the lock for {}",
+                    self.spaces_key
+                ))?;
+            if !workspace.read().lock_drift.is_empty() {
+                return Ok(());
+            }
+        }
+
+        let working_directory = self.get_working_directory_in_repo(workspace.clone());
+        let repository = git::Repository::new(self.url.clone(), working_directory.clone());
+        let is_existing = std::path::Path::new(working_directory.as_ref())
+            .join(".git")
+            .exists();
+        let dirty_policy = self
+            .dirty_policy
+            .unwrap_or_else(singleton::get_dirty_policy);
+        // with Fail the checkout runs as is, so the status is only needed for the other policies
+        let is_dirty = is_existing
+            && dirty_policy != git::DirtyPolicy::Fail
+            && repository.is_dirty(progress).context(format_context!(
+                "Failed to check {} for local changes",
+                self.spaces_key
+            ))?;
+
+        // catches a remote that was changed by hand after the checkout
+        if is_existing {
+            if let Some(remote_url) = repository.get_remote_url(progress) {
+                if !git::is_same_remote_url(remote_url.as_ref(), self.url.as_ref()) {
+                    logger(progress, self.url.clone()).warning(
+                        format!(
+                            "{} origin is {remote_url} but the checkout rule uses {}",
+                            self.spaces_key, self.url
+                        )
+                        .as_str(),
+                    );
+                }
+            }
+        }
+
+        let mut is_stashed = false;
+        if is_dirty {
+            logger(progress, self.url.clone()).info(
+                format!(
+                    "{} has local changes - dirty policy is {dirty_policy:?}",
+                    self.spaces_key
+                )
+                .as_str(),
+            );
+            match dirty_policy {
+                git::DirtyPolicy::Fail => {}
+                git::DirtyPolicy::Skip => {
+                    logger(progress, self.url.clone()).warning(
+                        format!("{} has local changes - skipping checkout", self.spaces_key)
+                            .as_str(),
+                    );
+                    return Ok(());
+                }
+                git::DirtyPolicy::Stash => {
+                    repository.stash_push(progress).context(format_context!(
+                        "{name} - Failed to stash local changes in {}",
+                        self.spaces_key
+                    ))?;
+                    is_stashed = true;
+                }
+            }
+        }
+
+        let checkout_result = self.execute_checkout(progress, workspace.clone(), name);
+
+        // restore the local changes even if the checkout failed
+        if is_stashed {
+            repository.stash_pop(progress).context(format_context!(
+                "{name} - Failed to restore local changes in {}",
+                self.spaces_key
+            ))?;
+        }
+        checkout_result?;
+
+        self.apply_patches(progress, working_directory, name)
+            .context(format_context!("Failed to apply patches to {}", self.spaces_key))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limited_git(revision: &str) -> Git {
+        Git {
+            url: "https://github.com/work-spaces/spaces".into(),
+            spaces_key: "spaces".into(),
+            worktree_path: "spaces".into(),
+            checkout: git::Checkout::Revision(revision.into()),
+            clone: git::Clone::Default,
+            is_evaluate_spaces_modules: false,
+            evaluate_spaces_modules_path: None,
+            sparse_checkout: None,
+            working_directory: None,
+            patches: None,
+            dirty_policy: None,
+            depth: Some(1),
+            shallow_since: None,
+        }
+    }
+
+    fn to_strings(arguments: Vec<Arc<str>>) -> Vec<String> {
+        arguments.iter().map(|argument| argument.to_string()).collect()
+    }
+
+    #[test]
+    fn limited_history_clone_arguments() {
+        let git = limited_git("v1.2.0");
+        assert_eq!(
+            to_strings(git.get_history_clone_arguments()),
+            vec!["--depth=1", "--branch", "v1.2.0"]
+        );
+        assert!(git.get_history_fetch_commit().is_none());
+
+        let git = limited_git("main:^1.0");
+        assert_eq!(
+            to_strings(git.get_history_clone_arguments()),
+            vec!["--depth=1", "--branch", "main"]
+        );
+
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        let git = limited_git(commit);
+        assert_eq!(to_strings(git.get_history_clone_arguments()), vec!["--depth=1"]);
+        assert_eq!(git.get_history_fetch_commit().as_deref(), Some(commit));
+
+        let mut git = limited_git("main");
+        git.depth = None;
+        assert!(git.get_history_clone_arguments().is_empty());
+        assert!(git.get_history_fetch_commit().is_none());
+    }
+
+    #[test]
+    fn frozen_lock_drift() {
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        let moved = "89abcdef0123456789abcdef0123456789abcdef";
+        let refs = |name: &str, commit: &str| -> Vec<(Arc<str>, Arc<str>)> {
+            vec![(commit.into(), name.into())]
+        };
+
+        // branches are locked to the commit the remote branch points to
+        let branch_refs = refs("refs/heads/main", commit);
+        assert!(get_lock_drift("spaces", "main", commit, &branch_refs).is_none());
+        let drift = get_lock_drift("spaces", "main", moved, &branch_refs).unwrap();
+        assert!(drift.contains(commit));
+
+        // tags are locked to the tag or the commit it points to
+        let mut tag_refs = refs("refs/tags/v1.0", moved);
+        tag_refs.extend(refs("refs/tags/v1.0^{}", commit));
+        assert!(get_lock_drift("spaces", "v1.0", "v1.0", &tag_refs).is_none());
+        assert!(get_lock_drift("spaces", "v1.0", commit, &tag_refs).is_none());
+        assert!(get_lock_drift("spaces", "v1.0", "v0.9", &tag_refs).is_some());
+        assert!(get_lock_drift("spaces", "v2.0", "v2.0", &tag_refs).is_some());
+
+        // commits don't need the remote
+        assert!(get_lock_drift("spaces", &commit[..7], commit, &[]).is_none());
+        assert!(get_lock_drift("spaces", commit, "v1.0", &[]).is_none());
+        assert!(get_lock_drift("spaces", commit, moved, &[]).is_some());
+    }
 }
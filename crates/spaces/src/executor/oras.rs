@@ -1,4 +1,4 @@
-use crate::workspace;
+use crate::{singleton, workspace};
 use anyhow::Context;
 use anyhow_source_location::{format_context, format_error};
 use serde::{Deserialize, Serialize};
@@ -128,6 +128,12 @@ impl OrasArchive {
         name: &str,
     ) -> anyhow::Result<()> {
         // download the manifest and get the digest
+        if singleton::get_is_offline() {
+            return Err(format_error!(
+                "--offline: {name} needs the oras manifest of {}",
+                self.get_artifact_label()
+            ));
+        }
 
         let manifest_details = self
             .get_manifest_details(&mut progress, workspace.clone())
@@ -184,3 +190,168 @@ impl OrasArchive {
         Ok(())
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OciImage {
+    pub registry: Arc<str>,
+    pub repository: Arc<str>,
+    pub digest: Arc<str>,
+    pub strip_prefix: Option<Arc<str>>,
+    pub add_prefix: Option<Arc<str>>,
+}
+
+impl OciImage {
+    fn get_layer_label(&self) -> Arc<str> {
+        format!("{}/{}@{}", self.registry, self.repository, self.digest).into()
+    }
+
+    /// The hex of a `sha256:<hex>` digest, OCI digests use lowercase hex
+    fn get_sha256(&self) -> anyhow::Result<Arc<str>> {
+        let sha256 = self.digest.strip_prefix("sha256:").ok_or(format_error!(
+            "OCI digest {} must be sha256:<hex>",
+            self.digest
+        ))?;
+        let is_hex = sha256.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'));
+        if sha256.len() != 64 || !is_hex {
+            return Err(format_error!(
+                "Invalid sha256 in OCI digest {}",
+                self.digest
+            ));
+        }
+        Ok(sha256.into())
+    }
+
+    fn fetch_layer(
+        &self,
+        progress: &mut printer::MultiProgressBar,
+        workspace: workspace::WorkspaceArc,
+        destination: &std::path::Path,
+        sha256: &str,
+    ) -> anyhow::Result<()> {
+        let layer_label = self.get_layer_label();
+        if singleton::get_is_offline() {
+            return Err(format_error!("--offline: {layer_label} is not in the store"));
+        }
+
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)
+                .context(format_context!("Failed to create {parent:?}"))?;
+        }
+
+        let options = printer::ExecuteOptions {
+            arguments: vec![
+                "blob".into(),
+                "fetch".into(),
+                format!("--output={}", destination.to_string_lossy()).into(),
+                layer_label.clone(),
+            ],
+            ..Default::default()
+        };
+
+        logger::Logger::new_progress(progress, layer_label.clone())
+            .debug(format!("Fetching layer using oras {}", options.arguments.join(" ")).as_str());
+
+        progress
+            .execute_process(
+                &get_oras_command(&workspace.read().get_spaces_tools_path()),
+                options,
+            )
+            .context(format_context!("failed to fetch {layer_label} using oras"))?;
+
+        let contents = std::fs::read(destination)
+            .context(format_context!("Failed to read {destination:?}"))?;
+        let actual_sha256 = sha256::digest(contents);
+        if actual_sha256 != sha256 {
+            let _ = std::fs::remove_file(destination);
+            return Err(format_error!(
+                "Digest mismatch for {layer_label}: got sha256:{actual_sha256}"
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn execute(
+        &self,
+        mut progress: printer::MultiProgressBar,
+        workspace: workspace::WorkspaceArc,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        let sha256 = self
+            .get_sha256()
+            .context(format_context!("bad digest for {name}"))?;
+
+        // image layers are gzip compressed tarballs
+        let archive = http_archive::Archive {
+            url: format!(
+                "oras://{}/{}/{sha256}.tar.gz",
+                self.registry, self.repository
+            )
+            .into(),
+            sha256: sha256.clone(),
+            add_prefix: self.add_prefix.clone(),
+            strip_prefix: self.strip_prefix.clone(),
+            ..Default::default()
+        };
+
+        let tools_path = format!("{}/sysroot/bin", workspace.read().get_spaces_tools_path());
+        let store_path = workspace.read().get_store_path();
+        let http_archive = http_archive::HttpArchive::new(&store_path, name, &archive, &tools_path)
+            .context(format_context!("Failed to create http_archive {archive:?}"))?;
+
+        let full_path = std::path::Path::new(&http_archive.full_path_to_archive);
+        if !full_path.exists() {
+            self.fetch_layer(&mut progress, workspace.clone(), full_path, &sha256)
+                .context(format_context!("Failed to fetch OCI layer for {name}"))?;
+        }
+
+        // sync will skip the download because the file is already there
+        let next_progress_bar = http_archive
+            .sync(progress)
+            .context(format_context!("Failed to sync http_archive {}", name))?;
+
+        let workspace_directory = workspace.read().absolute_path.clone();
+
+        http_archive
+            .create_links(next_progress_bar, workspace_directory.as_ref(), name)
+            .context(format_context!(
+                "Failed to create hard links for OCI image {}",
+                name
+            ))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_image(digest: &str) -> OciImage {
+        OciImage {
+            registry: "ghcr.io".into(),
+            repository: "work-spaces/tools".into(),
+            digest: digest.into(),
+            strip_prefix: None,
+            add_prefix: None,
+        }
+    }
+
+    #[test]
+    fn oci_digest_must_be_sha256_hex() {
+        let sha256 = "0123456789abcdef".repeat(4);
+        let get_sha256 = |digest: String| new_image(digest.as_str()).get_sha256();
+        assert_eq!(
+            get_sha256(format!("sha256:{sha256}")).unwrap().as_ref(),
+            sha256
+        );
+
+        assert!(get_sha256(sha256.clone()).is_err());
+        assert!(get_sha256(format!("sha512:{sha256}")).is_err());
+        assert!(get_sha256(format!("sha256:{}", &sha256[1..])).is_err());
+        // the hex is part of store paths, so nothing else is accepted
+        assert!(get_sha256(format!("sha256:../{}", &sha256[3..])).is_err());
+        assert!(get_sha256(format!("sha256:{}", sha256.to_uppercase())).is_err());
+    }
+}
@@ -69,4 +69,30 @@ impl Inputs {
     pub fn save_digest(&mut self, rule: &str, digest: Arc<str>) {
         self.inputs.insert(rule.into(), digest);
     }
+
+    pub fn remove_digest(&mut self, rule: &str) {
+        self.inputs.remove(rule);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digests_round_trip_through_the_file() {
+        let path = std::env::temp_dir().join(format!("spaces-inputs-{}", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+
+        let mut inputs = Inputs::new(path.as_str());
+        assert!(inputs.is_changed("//:build", "a".into()).unwrap().is_some());
+        inputs.save_digest("//:build", "a".into());
+        inputs.save(path.as_str()).unwrap();
+
+        let inputs = Inputs::new(path.as_str());
+        assert!(inputs.is_changed("//:build", "a".into()).unwrap().is_none());
+        assert!(inputs.is_changed("//:build", "b".into()).unwrap().is_some());
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
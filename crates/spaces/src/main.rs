@@ -2,8 +2,11 @@
 extern crate starlark;
 
 mod arguments;
+mod config;
 mod docs;
+mod doctor;
 mod evaluator;
+mod events;
 mod executor;
 mod builtins;
 mod label;
@@ -13,6 +16,7 @@ mod tools;
 mod runner;
 mod workspace;
 mod singleton;
+mod summary;
 //mod stardoc;
 
 fn main() -> anyhow::Result<()> {
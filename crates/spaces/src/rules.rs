@@ -1,4 +1,4 @@
-use crate::{executor, label, singleton, workspace};
+use crate::{events, executor, label, singleton, workspace};
 use anyhow::Context;
 use anyhow_source_location::{format_context, format_error};
 use clap::ValueEnum;
@@ -6,12 +6,32 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Condvar, Mutex};
 
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ListFormat {
+    Yaml,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum GraphFormat {
+    Dot,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShowFiles {
+    pub is_inputs: bool,
+    pub is_outputs: bool,
+    pub format: ListFormat,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
 pub enum Phase {
     Checkout,
     PostCheckout,
     Run,
     Evaluate,
+    Clean,
     Complete,
     Cancelled,
 }
@@ -28,12 +48,43 @@ pub enum RuleType {
 pub struct Rule {
     pub name: Arc<str>,
     pub deps: Option<Vec<Arc<str>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<Arc<str>>,
     pub help: Option<Arc<str>>,
     pub inputs: Option<HashSet<Arc<str>>>,
+    /// A rule with inputs also runs when these files are deleted or modified since its last
+    /// run. Rules without inputs always run.
     pub outputs: Option<HashSet<Arc<str>>>,
+    /// Folder names skipped when scanning the inputs of this rule (added to `.spaces/logs`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_folders: Option<Vec<Arc<str>>>,
+    /// Globs of the files `--export-artifacts` collects (default is the outputs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifacts: Option<HashSet<Arc<str>>>,
     pub platforms: Option<Vec<platform::Platform>>,
     #[serde(rename = "type")]
     pub type_: Option<RuleType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource: Option<Arc<str>>,
+}
+
+impl Rule {
+    pub fn get_skip_folders(&self) -> &[Arc<str>] {
+        self.skip_folders.as_deref().unwrap_or_default()
+    }
+
+    /// The one line summary of the rule, the first line of `help` is used if there is no `description`
+    pub fn get_description(&self) -> Option<Arc<str>> {
+        if let Some(description) = self.description.as_ref() {
+            return Some(description.clone());
+        }
+        self.help.as_ref().and_then(|help| {
+            help.lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .map(|line| line.into())
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -66,6 +117,11 @@ impl RuleSignal {
         }
     }
 
+    fn reset(&self) {
+        let (lock, _) = &*self.signal;
+        lock.lock().unwrap().ready = false;
+    }
+
     fn set_ready_notify_all(&self) {
         let (lock, cvar) = &*self.signal;
         let mut signal_access = lock.lock().unwrap();
@@ -74,6 +130,51 @@ impl RuleSignal {
     }
 }
 
+// limits how many rules sharing a resource execute at once (see info.set_resource_capacity)
+#[derive(Debug, Default)]
+struct ResourceSlots {
+    in_use: Mutex<HashMap<Arc<str>, i64>>,
+    condvar: Condvar,
+}
+
+static RESOURCE_SLOTS: state::InitCell<ResourceSlots> = state::InitCell::new();
+
+fn get_resource_slots() -> &'static ResourceSlots {
+    if let Some(slots) = RESOURCE_SLOTS.try_get() {
+        return slots;
+    }
+    RESOURCE_SLOTS.set(ResourceSlots::default());
+    RESOURCE_SLOTS.get()
+}
+
+struct ResourceGuard {
+    resource: Arc<str>,
+}
+
+impl ResourceGuard {
+    fn acquire(resource: Arc<str>) -> Self {
+        let capacity = singleton::get_resource_capacity(resource.as_ref());
+        let slots = get_resource_slots();
+        let mut in_use = slots.in_use.lock().unwrap();
+        while *in_use.get(&resource).unwrap_or(&0) >= capacity {
+            in_use = slots.condvar.wait(in_use).unwrap();
+        }
+        *in_use.entry(resource.clone()).or_insert(0) += 1;
+        Self { resource }
+    }
+}
+
+impl Drop for ResourceGuard {
+    fn drop(&mut self) {
+        let slots = get_resource_slots();
+        let mut in_use = slots.in_use.lock().unwrap();
+        if let Some(count) = in_use.get_mut(&self.resource) {
+            *count -= 1;
+        }
+        slots.condvar.notify_all();
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub executor: executor::Task,
@@ -141,24 +242,12 @@ impl Task {
         let signal = self.signal.clone();
         let rule = self.rule.clone();
         let deps_signals = self.deps_signals.clone();
+        let phase = self.phase;
 
         progress.set_message(format!("Waiting for dependencies ({:?})", self.phase).as_str());
 
         std::thread::spawn(move || -> anyhow::Result<executor::TaskResult> {
-            // check inputs/outputs to see if we need to run
             let mut skip_execute_message = None;
-            if let (Some(platforms), Some(current_platform)) =
-                (rule.platforms.as_ref(), platform::Platform::get_platform())
-            {
-                if !platforms.contains(&current_platform) {
-                    skip_execute_message = Some(format!("Skipping {name}: platform not enabled"));
-                }
-            }
-
-            logger::Logger::new_progress(&mut progress, name.clone()).trace(
-                format!("Skip execute message after platform check? {skip_execute_message:?}")
-                    .as_str(),
-            );
 
             let total = deps_signals.len();
 
@@ -186,6 +275,7 @@ impl Task {
             logger::Logger::new_progress(&mut progress, name.clone())
                 .debug(format!("{name} All dependencies are done").as_str());
 
+            let current_rule;
             {
                 logger::Logger::new_progress(&mut progress, name.clone())
                     .debug(format!("{name} check for skipping/cancelation").as_str());
@@ -199,58 +289,60 @@ impl Task {
                         .debug(format!("Skipping {name}: cancelled").as_str());
                     skip_execute_message =
                         Some(format!("Skipping {name} because it was cancelled"));
-                } else if task.rule.type_ == Some(RuleType::Optional) {
-                    logger::Logger::new_progress(&mut progress, name.clone())
-                        .debug(format!("Skipping {name} because it is optional").as_str());
-                    skip_execute_message = Some(format!("Skipping {name}: optional"));
                 }
+                // the rule type can change after sorting (e.g. enabled optional rules)
+                current_rule = task.rule.clone();
                 logger::Logger::new_progress(&mut progress, name.clone())
                     .trace(format!("{name} done checking skip cancellation").as_str());
             }
 
             let rule_name = rule.name.clone();
-
-            let updated_digest = if let Some(inputs) = &rule.inputs {
-                logger::Logger::new_progress(&mut progress, name.clone())
-                    .trace(format!("{name} update workspace changes").as_str());
-
-                workspace
-                    .write()
-                    .update_changes(&mut progress, inputs)
-                    .context(format_context!("Failed to update workspace changes"))?;
-
-                logger::Logger::new_progress(&mut progress, name.clone())
-                    .trace(format!("{name} check for new digest").as_str());
-
-                let seed = serde_json::to_string(&executor)
-                    .context(format_context!("Failed to serialize"))?;
-                let digest = workspace
-                    .read()
-                    .is_rule_inputs_changed(&mut progress, &rule_name, seed.as_str(), inputs)
-                    .context(format_context!("Failed to check inputs for {rule_name}"))?;
-                if digest.is_none() {
-                    // the digest has not changed - not need to execute
-                    skip_execute_message = Some(format!("Skipping {name}: same inputs"));
+            let workspace_root =
+                std::path::PathBuf::from(workspace.read().get_absolute_path().as_ref());
+
+            let mut updated_digest = None;
+            if skip_execute_message.is_none() {
+                match get_run_decision(
+                    &mut progress,
+                    workspace.clone(),
+                    &current_rule,
+                    &executor,
+                    false,
+                )? {
+                    RunDecision::Skip { reason } => {
+                        skip_execute_message = Some(format!("Skipping {name}: {reason}"));
+                    }
+                    RunDecision::Run { reason, digest } => {
+                        logger::Logger::new_progress(&mut progress, name.clone())
+                            .debug(format!("Running {name}: {reason}").as_str());
+                        updated_digest = digest;
+                    }
                 }
-                logger::Logger::new_progress(&mut progress, name.clone())
-                    .debug(format!("New digest for {rule_name}={digest:?}").as_str());
-                digest
-            } else {
-                None
-            };
+            }
 
             if let Some(skip_message) = skip_execute_message.as_ref() {
                 logger::Logger::new_progress(&mut progress, name.clone())
                     .info(skip_message.as_str());
                 progress.set_message(skip_message);
+                events::emit(events::Event::TaskSkipped {
+                    name: name.clone(),
+                    reason: skip_message.as_str().into(),
+                });
             } else {
                 progress.set_message("Running");
+                events::emit(events::Event::TaskStarted { name: name.clone() });
             }
 
             // time how long it takes to execute the task
             let start_time = std::time::Instant::now();
 
             let task_result = if skip_execute_message.is_none() {
+                let _resource_guard = rule.resource.clone().map(|resource| {
+                    progress.set_message(format!("Waiting for resource {resource}").as_str());
+                    let guard = ResourceGuard::acquire(resource);
+                    progress.set_message("Running");
+                    guard
+                });
                 executor
                     .execute(progress, workspace.clone(), &rule_name)
                     .context(format_context!("Failed to exec {}", name))
@@ -259,14 +351,44 @@ impl Task {
             };
 
             let elapsed_time = start_time.elapsed();
-            workspace
-                .write()
-                .update_rule_metrics(&rule_name, elapsed_time);
+            let status = if skip_execute_message.is_some() {
+                workspace::RuleStatus::Skipped
+            } else if task_result.is_ok() {
+                workspace::RuleStatus::Success
+            } else {
+                workspace::RuleStatus::Failure
+            };
+            workspace.write().update_rule_metrics(
+                &rule_name,
+                phase,
+                elapsed_time,
+                status,
+                skip_execute_message.as_deref().map(Arc::from),
+            );
+
+            if skip_execute_message.is_none() {
+                events::emit(events::Event::TaskFinished {
+                    name: name.clone(),
+                    status,
+                    duration_ms: elapsed_time.as_millis(),
+                    log_file: workspace.read().get_log_file(&rule_name),
+                });
+            }
 
             if task_result.is_ok() {
                 if let Some(digest) = updated_digest {
                     workspace.write().update_rule_digest(&rule_name, digest);
                 }
+
+                if let (Some(_), Some(outputs), None) =
+                    (&rule.inputs, &rule.outputs, skip_execute_message.as_ref())
+                {
+                    let outputs_digest = get_outputs_digest(workspace_root.as_path(), outputs)
+                        .context(format_context!("Failed to digest outputs for {rule_name}"))?;
+                    workspace
+                        .write()
+                        .update_rule_outputs_digest(&rule_name, outputs_digest);
+                }
             }
 
             // before notifying dependents process the enabled_targets list
@@ -307,11 +429,252 @@ impl Task {
     }
 }
 
+fn get_output_paths(
+    workspace_root: &std::path::Path,
+    outputs: &HashSet<Arc<str>>,
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    // outputs use the same +/- convention as inputs, a bare path is an include
+    let patterns: Vec<String> = outputs
+        .iter()
+        .map(|output| {
+            if let Some(exclude) = output.strip_prefix('-') {
+                format!("!{exclude}")
+            } else {
+                output.strip_prefix('+').unwrap_or(output).to_string()
+            }
+        })
+        .collect();
+
+    let walker = globwalk::GlobWalkerBuilder::from_patterns(workspace_root, &patterns)
+        .build()
+        .context(format_context!("Invalid outputs {outputs:?}"))?;
+
+    let mut paths: Vec<std::path::PathBuf> =
+        walker.flatten().map(|entry| entry.into_path()).collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Resolves the paths to remove for `spaces clean`. Fails without removing anything if
+/// any existing path is the workspace itself or resolves outside of it.
+fn get_clean_paths(
+    workspace_root: &std::path::Path,
+    paths: Vec<std::path::PathBuf>,
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    let mut clean_paths = Vec::new();
+    for path in paths {
+        let Ok(canonical_path) = path.canonicalize() else {
+            // already removed or never created
+            continue;
+        };
+
+        if !canonical_path.starts_with(workspace_root) || canonical_path == workspace_root {
+            return Err(format_error!("{path:?} is not inside the workspace {workspace_root:?}"));
+        }
+        clean_paths.push(canonical_path);
+    }
+    Ok(clean_paths)
+}
+
+/// Returns the declared output includes that do not match anything on disk
+fn get_missing_outputs(
+    workspace_root: &std::path::Path,
+    outputs: &HashSet<Arc<str>>,
+) -> anyhow::Result<Vec<Arc<str>>> {
+    let excludes: Vec<Arc<str>> = outputs
+        .iter()
+        .filter(|output| output.starts_with('-'))
+        .cloned()
+        .collect();
+
+    let mut missing = Vec::new();
+    for output in outputs.iter().filter(|output| !output.starts_with('-')) {
+        let mut single_output: HashSet<Arc<str>> = excludes.iter().cloned().collect();
+        single_output.insert(output.clone());
+        if get_output_paths(workspace_root, &single_output)?.is_empty() {
+            missing.push(output.clone());
+        }
+    }
+    missing.sort();
+    Ok(missing)
+}
+
+fn get_outputs_digest(
+    workspace_root: &std::path::Path,
+    outputs: &HashSet<Arc<str>>,
+) -> anyhow::Result<Arc<str>> {
+    let mut hasher = blake3::Hasher::new();
+    for path in get_output_paths(workspace_root, outputs)? {
+        if path.is_file() {
+            let contents =
+                std::fs::read(&path).context(format_context!("Failed to read output {path:?}"))?;
+            hasher.update(path.to_string_lossy().as_bytes());
+            hasher.update(blake3::hash(&contents).as_bytes());
+        }
+    }
+    Ok(hasher.finalize().to_string().into())
+}
+
+/// The phase and type of each task, both can change while the tasks execute
+type TaskStates = HashMap<Arc<str>, (Phase, Option<RuleType>)>;
+
+fn get_task_states(tasks: &HashMap<Arc<str>, Task>) -> TaskStates {
+    tasks
+        .iter()
+        .map(|(name, task)| (name.clone(), (task.phase, task.rule.type_)))
+        .collect()
+}
+
+/// Puts the tasks back in their initial state so the same sorted graph can execute again
+fn restore_task_states(tasks: &mut HashMap<Arc<str>, Task>, states: &TaskStates) {
+    for (name, task) in tasks.iter_mut() {
+        if let Some((phase, type_)) = states.get(name) {
+            task.phase = *phase;
+            task.rule.type_ = *type_;
+        }
+        task.signal.reset();
+    }
+}
+
+enum RunDecision {
+    Run {
+        reason: &'static str,
+        /// the new inputs digest, saved after the rule succeeds
+        digest: Option<Arc<str>>,
+    },
+    Skip {
+        reason: &'static str,
+    },
+}
+
+/// Decides if a rule runs. Execution and `--dry-run` share this so the plan matches the run.
+/// `is_dependency_pending` is true when a dependency will run before this rule (dry run).
+fn get_run_decision(
+    progress: &mut printer::MultiProgressBar,
+    workspace: workspace::WorkspaceArc,
+    rule: &Rule,
+    executor: &executor::Task,
+    is_dependency_pending: bool,
+) -> anyhow::Result<RunDecision> {
+    if let (Some(platforms), Some(current_platform)) =
+        (rule.platforms.as_ref(), platform::Platform::get_platform())
+    {
+        if !platforms.contains(&current_platform) {
+            return Ok(RunDecision::Skip {
+                reason: "platform not enabled",
+            });
+        }
+    }
+
+    if rule.type_ == Some(RuleType::Optional) {
+        return Ok(RunDecision::Skip { reason: "optional" });
+    }
+
+    if let Some(inputs) = &rule.inputs {
+        workspace
+            .write()
+            .update_changes(progress, inputs, rule.get_skip_folders())
+            .context(format_context!("Failed to update workspace changes"))?;
+
+        let seed =
+            serde_json::to_string(executor).context(format_context!("Failed to serialize"))?;
+        let digest = workspace
+            .read()
+            .is_rule_inputs_changed(
+                progress,
+                &rule.name,
+                seed.as_str(),
+                inputs,
+                rule.get_skip_folders(),
+            )
+            .context(format_context!("Failed to check inputs for {}", rule.name))?;
+
+        let is_outputs_changed = if let Some(outputs) = &rule.outputs {
+            let workspace_path = workspace.read().get_absolute_path();
+            let outputs_digest =
+                get_outputs_digest(std::path::Path::new(workspace_path.as_ref()), outputs)
+                    .context(format_context!(
+                        "Failed to digest outputs for {}",
+                        rule.name
+                    ))?;
+            workspace
+                .read()
+                .is_rule_outputs_changed(&rule.name, outputs_digest.as_ref())
+        } else {
+            false
+        };
+
+        let reason = if digest.is_some() {
+            "inputs changed"
+        } else if singleton::get_is_ignore_inputs() {
+            "inputs ignored"
+        } else if is_outputs_changed {
+            // outputs that were deleted or modified since the last run make the rule dirty
+            "outputs changed"
+        } else if is_dependency_pending {
+            "inputs may be changed by a dependency"
+        } else {
+            return Ok(RunDecision::Skip {
+                reason: "same inputs",
+            });
+        };
+        return Ok(RunDecision::Run { reason, digest });
+    }
+
+    Ok(RunDecision::Run {
+        reason: "no inputs",
+        digest: None,
+    })
+}
+
 pub fn get_sanitized_rule_name(rule_name: Arc<str>) -> Arc<str> {
     let state = get_state().read();
     state.get_sanitized_rule_name(rule_name)
 }
 
+/// The rule (deps, platforms, ...) and the executor of a repo task, compared when a second
+/// script adds the same repo
+fn get_repo_definition(task: &Task) -> anyhow::Result<serde_json::Value> {
+    Ok(serde_json::json!({
+        "rule": serde_json::to_value(&task.rule)?,
+        "executor": serde_json::to_value(&task.executor)?,
+    }))
+}
+
+/// Collects `path: existing -> new` for each value that differs between two serialized tasks
+fn get_json_differences(
+    path: &str,
+    existing: &serde_json::Value,
+    new: &serde_json::Value,
+    differences: &mut Vec<String>,
+) {
+    match (existing, new) {
+        (serde_json::Value::Object(existing_map), serde_json::Value::Object(new_map)) => {
+            let mut keys: Vec<&String> = existing_map.keys().chain(new_map.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let key_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                get_json_differences(
+                    key_path.as_str(),
+                    existing_map.get(key).unwrap_or(&serde_json::Value::Null),
+                    new_map.get(key).unwrap_or(&serde_json::Value::Null),
+                    differences,
+                );
+            }
+        }
+        _ => {
+            if existing != new {
+                differences.push(format!("  {path}: {existing} -> {new}"));
+            }
+        }
+    }
+}
+
 pub fn insert_task(task: Task) -> anyhow::Result<()> {
     let state = get_state().read();
     state.insert_task(task)
@@ -323,14 +686,128 @@ pub fn set_latest_starlark_module(name: Arc<str>) {
     state.all_modules.insert(name);
 }
 
-pub fn show_tasks(printer: &mut printer::Printer) -> anyhow::Result<()> {
+pub fn show_tasks(printer: &mut printer::Printer, format: ListFormat) -> anyhow::Result<()> {
     let state = get_state().read();
-    state.show_tasks(printer)
+    match format {
+        ListFormat::Yaml => state.show_tasks(printer),
+        ListFormat::Json => state.show_tasks_json(),
+    }
 }
 
-pub fn sort_tasks(target: Option<Arc<str>>, phase: Phase) -> anyhow::Result<()> {
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+// leaves room for the rule name key when the help is shown
+const HELP_INDENT: usize = 4;
+
+fn get_terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse::<usize>().ok())
+        .filter(|columns| *columns > HELP_INDENT)
+        .unwrap_or(DEFAULT_TERMINAL_WIDTH)
+}
+
+/// Wraps `text` on word boundaries so each line fits within `width` columns
+/// (less the help indent). Existing line breaks are kept and words longer than
+/// the width are not split.
+fn wrap_text(text: &str, width: usize) -> Arc<str> {
+    let width = width.saturating_sub(HELP_INDENT).max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines.join("\n").into()
+}
+
+/// Returns `deps` with entries that contain `*` replaced by the names of the matching rules
+/// (sorted). The rule keeps the glob so it is expanded again when more rules are added.
+fn get_expanded_deps(
+    rule_name: &str,
+    deps: &[Arc<str>],
+    rule_names: &[Arc<str>],
+) -> anyhow::Result<Vec<Arc<str>>> {
+    let mut expanded = Vec::new();
+    let mut known_deps = HashSet::new();
+    for dep in deps.iter() {
+        let matches = if dep.contains('*') {
+            let globs: HashSet<Arc<str>> = HashSet::from([format!("+{dep}").into()]);
+            let mut matches: Vec<Arc<str>> = rule_names
+                .iter()
+                .filter(|name| name.as_ref() != rule_name)
+                .filter(|name| changes::glob::match_globs(&globs, name))
+                .cloned()
+                .collect();
+            if matches.is_empty() {
+                return Err(format_error!(
+                    "Dependency {dep} of {rule_name} does not match any rules"
+                ));
+            }
+            matches.sort();
+            matches
+        } else {
+            vec![dep.clone()]
+        };
+        for dep in matches {
+            if known_deps.insert(dep.clone()) {
+                expanded.push(dep);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+pub fn sort_tasks(targets: &[Arc<str>], phase: Phase) -> anyhow::Result<()> {
     let mut state = get_state().write();
-    state.sort_tasks(target, phase)
+    state.sort_tasks(targets, phase)
+}
+
+fn get_edit_distance(first: &str, second: &str) -> usize {
+    let second: Vec<char> = second.chars().collect();
+    let mut previous: Vec<usize> = (0..=second.len()).collect();
+    for (first_index, first_char) in first.chars().enumerate() {
+        let mut current = vec![first_index + 1; second.len() + 1];
+        for (second_index, second_char) in second.iter().enumerate() {
+            let substitution = previous[second_index] + usize::from(first_char != *second_char);
+            current[second_index + 1] = substitution
+                .min(previous[second_index + 1] + 1)
+                .min(current[second_index] + 1);
+        }
+        previous = current;
+    }
+    previous[second.len()]
+}
+
+fn get_target_suggestions(tasks: &HashMap<Arc<str>, Task>, target: &str) -> Vec<Arc<str>> {
+    const MAX_SUGGESTIONS: usize = 3;
+    let mut suggestions: Vec<(usize, Arc<str>)> = tasks
+        .keys()
+        .filter_map(|name| {
+            // compare against the rule name without the module label
+            let short_name = name.rsplit(':').next().unwrap_or(name);
+            let distance =
+                get_edit_distance(short_name, target).min(get_edit_distance(name, target));
+            if distance <= 3 || name.contains(target) {
+                Some((distance, name.clone()))
+            } else {
+                None
+            }
+        })
+        .collect();
+    suggestions.sort();
+    suggestions
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| name)
+        .collect()
 }
 
 pub fn execute(
@@ -342,6 +819,62 @@ pub fn execute(
     state.execute(printer, workspace, phase)
 }
 
+pub fn clean(
+    printer: &mut printer::Printer,
+    workspace: workspace::WorkspaceArc,
+) -> anyhow::Result<()> {
+    let state = get_state().read();
+    state.clean(printer, workspace)
+}
+
+pub fn export_artifacts(
+    printer: &mut printer::Printer,
+    workspace: workspace::WorkspaceArc,
+    directory: &str,
+) -> anyhow::Result<()> {
+    let state = get_state().read();
+    state.export_artifacts(printer, workspace, directory)
+}
+
+pub fn execute_repeat(
+    printer: &mut printer::Printer,
+    workspace: workspace::WorkspaceArc,
+    phase: Phase,
+    count: i64,
+) -> anyhow::Result<()> {
+    let state = get_state().read();
+    state.execute_repeat(printer, workspace, phase, count)
+}
+
+pub fn show_checkout_plan(printer: &mut printer::Printer) -> anyhow::Result<()> {
+    let state = get_state().read();
+    state.show_checkout_plan(printer)
+}
+
+pub fn show_files(
+    printer: &mut printer::Printer,
+    workspace: workspace::WorkspaceArc,
+    targets: &[Arc<str>],
+    show_files: ShowFiles,
+) -> anyhow::Result<()> {
+    let state = get_state().read();
+    state.show_files(printer, workspace, targets, show_files)
+}
+
+pub fn show_graph(format: GraphFormat) -> anyhow::Result<()> {
+    let state = get_state().read();
+    state.show_graph(format)
+}
+
+pub fn dry_run(
+    printer: &mut printer::Printer,
+    workspace: workspace::WorkspaceArc,
+    phase: Phase,
+) -> anyhow::Result<()> {
+    let state = get_state().read();
+    state.dry_run(printer, workspace, phase)
+}
+
 pub fn debug_sorted_tasks(printer: &mut printer::Printer, phase: Phase) -> anyhow::Result<()> {
     let state = get_state().read();
     for node_index in state.sorted.iter() {
@@ -387,10 +920,26 @@ impl State {
 
         let mut tasks = self.tasks.write();
 
-        if let Some(task) = tasks.get(&rule_label) {
-            return Err(format_error!(
-                "Rule already exists {rule_label} with {task:?}"
-            ));
+        if let Some(existing_task) = tasks.get(&rule_label) {
+            // several scripts can ask for the same repo, only a different definition is an error
+            if let (executor::Task::Git(_), executor::Task::Git(_)) =
+                (&existing_task.executor, &task.executor)
+            {
+                let existing_value = get_repo_definition(existing_task)
+                    .context(format_context!("Failed to serialize {rule_label}"))?;
+                let new_value = get_repo_definition(&task)
+                    .context(format_context!("Failed to serialize {rule_label}"))?;
+                if existing_value == new_value {
+                    return Ok(());
+                }
+                let mut differences = Vec::new();
+                get_json_differences("", &existing_value, &new_value, &mut differences);
+                return Err(format_error!(
+                    "Repo {rule_label} is already defined differently:\n{}",
+                    differences.join("\n")
+                ));
+            }
+            return Err(format_error!("Rule already exists {rule_label} with {existing_task:?}"));
         } else {
             tasks.insert(rule_label, task);
         }
@@ -398,9 +947,25 @@ impl State {
         Ok(())
     }
 
-    pub fn sort_tasks(&mut self, target: Option<Arc<str>>, phase: Phase) -> anyhow::Result<()> {
+    pub fn sort_tasks(&mut self, targets: &[Arc<str>], phase: Phase) -> anyhow::Result<()> {
         let mut tasks = self.tasks.write();
 
+        let missing_targets: Vec<String> = targets
+            .iter()
+            .filter(|target| !tasks.contains_key(*target))
+            .map(|target| {
+                let suggestions = get_target_suggestions(&tasks, target);
+                if suggestions.is_empty() {
+                    target.to_string()
+                } else {
+                    format!("{target} (did you mean {})", suggestions.join(", "))
+                }
+            })
+            .collect();
+        if !missing_targets.is_empty() {
+            return Err(format_error!("Target not found: {}", missing_targets.join("; ")));
+        }
+
         let setup_tasks = tasks
             .values()
             .filter(|task| task.rule.type_ == Some(RuleType::Setup))
@@ -448,6 +1013,26 @@ impl State {
             task_hasher.update(task.calculate_digest().as_bytes());
 
             if let Some(deps) = task.rule.deps.clone() {
+                let deps = if deps.iter().any(|dep| dep.contains('*')) {
+                    if task_phase == Phase::Checkout {
+                        // checkout rules run before later checkout waves add their rules
+                        return Err(format_error!(
+                            "Checkout task {} cannot use glob deps, only run rules can",
+                            task.rule.name
+                        ));
+                    }
+                    // run rules are sorted after the final checkout wave
+                    let rule_names: Vec<Arc<str>> = tasks_copy
+                        .values()
+                        .filter(|other_task| other_task.phase == task_phase)
+                        .map(|other_task| other_task.rule.name.clone())
+                        .collect();
+                    get_expanded_deps(&task.rule.name, &deps, &rule_names).context(
+                        format_context!("Failed to expand deps of {}", task.rule.name),
+                    )?
+                } else {
+                    deps
+                };
                 for dep in deps {
                     let dep_task = tasks_copy.get(&dep).ok_or(format_error!(
                         "Task Depedency not found: {dep} specified by {}",
@@ -498,14 +1083,28 @@ impl State {
             task.digest = task_hasher.finalize().to_string().into();
         }
 
-        let target_is_some = target.is_some();
+        // only checkout dependencies are connected during the checkout phase
+        let cycles = self.graph.get_cycles();
+        if !cycles.is_empty() {
+            let phase_name = if phase == Phase::Checkout {
+                "checkout"
+            } else {
+                "run"
+            };
+            let cycles: Vec<String> = cycles.iter().map(|cycle| cycle.join(", ")).collect();
+            return Err(format_error!(
+                "Circular dependency between {phase_name} rules: {}",
+                cycles.join("; ")
+            ));
+        }
 
         self.sorted = self
             .graph
-            .get_sorted_tasks(target)
+            .get_sorted_tasks(targets)
             .context(format_context!("Failed to sort tasks"))?;
 
-        if target_is_some {
+        let is_include_optional = phase == Phase::Run && singleton::get_is_include_optional();
+        if !targets.is_empty() || is_include_optional {
             // enable any optional tasks in the graph
             for node_index in self.sorted.iter() {
                 let task_name = self.graph.get_task(*node_index);
@@ -532,9 +1131,15 @@ impl State {
 
             if printer.verbosity.level == printer::Level::Debug {
                 printer.debug(task_name, &task)?;
-            } else if printer.verbosity.level <= printer::Level::Message || task.rule.help.is_some()
-            {
-                task_info_list.insert(task.rule.name.clone(), task.rule.help.clone());
+            } else {
+                // the full help is shown with the task details at the debug level
+                let description = task.rule.get_description();
+                if printer.verbosity.level <= printer::Level::Message || description.is_some() {
+                    let description = description
+                        .as_ref()
+                        .map(|description| wrap_text(description, get_terminal_width()));
+                    task_info_list.insert(task.rule.name.clone(), description);
+                }
             }
         }
 
@@ -543,6 +1148,545 @@ impl State {
         Ok(())
     }
 
+    /// Writes the details of the sorted tasks to stdout as JSON. The fields are
+    /// always present so tools can rely on the schema.
+    pub fn show_tasks_json(&self) -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct TaskDetails {
+            name: Arc<str>,
+            phase: Phase,
+            #[serde(rename = "type")]
+            type_: Option<RuleType>,
+            deps: Vec<Arc<str>>,
+            platforms: Vec<platform::Platform>,
+            inputs: Vec<Arc<str>>,
+            help: Option<Arc<str>>,
+        }
+
+        let tasks = self.tasks.read();
+        let mut task_details_list = Vec::new();
+        for node_index in self.sorted.iter() {
+            let task_name = self.graph.get_task(*node_index);
+            let task = tasks
+                .get(task_name)
+                .ok_or(format_error!("Task not found {task_name}"))?;
+            let mut inputs: Vec<Arc<str>> = task.rule.inputs.iter().flatten().cloned().collect();
+            inputs.sort();
+            task_details_list.push(TaskDetails {
+                name: task.rule.name.clone(),
+                phase: task.phase,
+                type_: task.rule.type_,
+                deps: task.rule.deps.clone().unwrap_or_default(),
+                platforms: task.rule.platforms.clone().unwrap_or_default(),
+                inputs,
+                help: task.rule.help.clone(),
+            });
+        }
+
+        use std::io::Write;
+        let content = serde_json::to_string_pretty(&task_details_list)
+            .context(format_context!("Failed to serialize tasks"))?;
+        writeln!(std::io::stdout(), "{content}").context(format_context!("Failed to write tasks"))?;
+        Ok(())
+    }
+
+    /// Removes `build/<rule>` and the declared outputs of the sorted run rules
+    /// and clears their inputs digests so they execute on the next run.
+    pub fn clean(
+        &self,
+        printer: &mut printer::Printer,
+        workspace: workspace::WorkspaceArc,
+    ) -> anyhow::Result<()> {
+        let workspace_path = workspace.read().get_absolute_path();
+        let workspace_root = std::path::Path::new(workspace_path.as_ref())
+            .canonicalize()
+            .context(format_context!("Failed to resolve workspace path {workspace_path}"))?;
+
+        let mut paths = Vec::new();
+        let mut cleaned_tasks = Vec::new();
+        let tasks = self.tasks.read();
+        for node_index in self.sorted.iter() {
+            let task_name = self.graph.get_task(*node_index);
+            let task = tasks
+                .get(task_name)
+                .ok_or(format_error!("Task not found {task_name}"))?;
+            if task.phase != Phase::Run {
+                continue;
+            }
+
+            paths.push(std::path::PathBuf::from(format!(
+                "{}/{}",
+                workspace::build_directory(),
+                task.rule.name
+            )));
+
+            if let Some(outputs) = task.rule.outputs.as_ref() {
+                paths.extend(
+                    get_output_paths(workspace_root.as_path(), outputs)
+                        .context(format_context!("Invalid outputs for {task_name}"))?,
+                );
+            }
+
+            cleaned_tasks.push(task_name.clone());
+        }
+
+        // every path is checked before anything is removed
+        let paths = get_clean_paths(workspace_root.as_path(), paths)
+            .context(format_context!("Refusing to clean {workspace_path}"))?;
+
+        for path in paths {
+            logger::Logger::new_printer(printer, "clean".into())
+                .info(format!("Removing {path:?}").as_str());
+
+            if path.is_dir() {
+                std::fs::remove_dir_all(&path)
+                    .context(format_context!("Failed to remove {path:?}"))?;
+            } else {
+                std::fs::remove_file(&path)
+                    .context(format_context!("Failed to remove {path:?}"))?;
+            }
+        }
+
+        for task_name in cleaned_tasks {
+            workspace.write().clear_rule_digest(&task_name);
+        }
+
+        workspace
+            .read()
+            .save_inputs()
+            .context(format_context!("Failed to save inputs"))?;
+
+        Ok(())
+    }
+
+    /// Executes the sorted tasks `count` times and reports how many runs passed.
+    /// Task phases and signals are restored between runs so the same graph is reused.
+    pub fn execute_repeat(
+        &self,
+        printer: &mut printer::Printer,
+        workspace: workspace::WorkspaceArc,
+        phase: Phase,
+        count: i64,
+    ) -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct RepeatRun {
+            run: i64,
+            passed: bool,
+            duration_ms: u128,
+        }
+
+        #[derive(Serialize)]
+        struct RepeatSummary {
+            passed: usize,
+            failed: usize,
+            min_duration_ms: u128,
+            mean_duration_ms: u128,
+            max_duration_ms: u128,
+            runs: Vec<RepeatRun>,
+        }
+
+        let initial_states = get_task_states(&self.tasks.read());
+
+        let mut runs = Vec::new();
+        for run in 1..=count {
+            restore_task_states(&mut self.tasks.write(), &initial_states);
+
+            logger::Logger::new_printer(printer, "repeat".into())
+                .message(format!("Run {run}/{count}").as_str());
+
+            let start_time = std::time::Instant::now();
+            let result = self.execute(printer, workspace.clone(), phase);
+            let duration_ms = start_time.elapsed().as_millis();
+            if let Err(error) = result.as_ref() {
+                logger::Logger::new_printer(printer, "repeat".into())
+                    .warning(format!("Run {run}/{count} failed: {error}").as_str());
+            }
+            runs.push(RepeatRun {
+                run,
+                passed: result.is_ok(),
+                duration_ms,
+            });
+        }
+
+        let durations: Vec<u128> = runs.iter().map(|run| run.duration_ms).collect();
+        let passed = runs.iter().filter(|run| run.passed).count();
+        let failed = runs.len() - passed;
+        let summary = RepeatSummary {
+            passed,
+            failed,
+            min_duration_ms: durations.iter().copied().min().unwrap_or_default(),
+            mean_duration_ms: durations.iter().sum::<u128>() / durations.len().max(1) as u128,
+            max_duration_ms: durations.iter().copied().max().unwrap_or_default(),
+            runs,
+        };
+        printer.info("repeat", &summary)?;
+
+        if failed > 0 {
+            return Err(format_error!("{failed} of {count} runs failed"));
+        }
+
+        Ok(())
+    }
+
+    /// Copies the artifacts (or the declared outputs) of the sorted run rules to
+    /// `directory/<rule>/` and writes `manifest.json` listing where each artifact came from.
+    pub fn export_artifacts(
+        &self,
+        printer: &mut printer::Printer,
+        workspace: workspace::WorkspaceArc,
+        directory: &str,
+    ) -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct ManifestEntry {
+            rule: Arc<str>,
+            source: Arc<str>,
+            destination: Arc<str>,
+        }
+
+        let workspace_path = workspace.read().get_absolute_path();
+        let workspace_root = std::path::Path::new(workspace_path.as_ref());
+        let export_root = std::path::Path::new(directory);
+
+        let mut manifest = Vec::new();
+        let tasks = self.tasks.read();
+        for node_index in self.sorted.iter() {
+            let task_name = self.graph.get_task(*node_index);
+            let task = tasks
+                .get(task_name)
+                .ok_or(format_error!("Task not found {task_name}"))?;
+            if task.phase != Phase::Run || task.rule.type_ == Some(RuleType::Optional) {
+                continue;
+            }
+            let Some(artifacts) = task.rule.artifacts.as_ref().or(task.rule.outputs.as_ref()) else {
+                continue;
+            };
+
+            // rules are namespaced by their label so outputs with the same name do not collide
+            let rule_directory = task.rule.name.trim_start_matches("//").replace(':', "/");
+            for path in get_output_paths(workspace_root, artifacts)? {
+                if !path.is_file() {
+                    continue;
+                }
+                let relative_path = path.strip_prefix(workspace_root).unwrap_or(&path);
+                let destination = export_root.join(&rule_directory).join(relative_path);
+                if let Some(parent) = destination.parent() {
+                    std::fs::create_dir_all(parent)
+                        .context(format_context!("Failed to create directory {parent:?}"))?;
+                }
+                if destination.exists() {
+                    std::fs::remove_file(&destination)
+                        .context(format_context!("Failed to remove {destination:?}"))?;
+                }
+                // hard links are free when the export is on the same file system
+                if std::fs::hard_link(&path, &destination).is_err() {
+                    std::fs::copy(&path, &destination).context(format_context!(
+                        "Failed to copy {path:?} to {destination:?}"
+                    ))?;
+                }
+
+                manifest.push(ManifestEntry {
+                    rule: task.rule.name.clone(),
+                    source: relative_path.to_string_lossy().into(),
+                    destination: destination
+                        .strip_prefix(export_root)
+                        .unwrap_or(&destination)
+                        .to_string_lossy()
+                        .into(),
+                });
+            }
+        }
+
+        std::fs::create_dir_all(export_root)
+            .context(format_context!("Failed to create directory {directory}"))?;
+        let manifest_path = export_root.join("manifest.json");
+        let content = serde_json::to_string_pretty(&manifest)
+            .context(format_context!("Failed to serialize the artifacts manifest"))?;
+        std::fs::write(&manifest_path, content)
+            .context(format_context!("Failed to write {manifest_path:?}"))?;
+
+        logger::Logger::new_printer(printer, "export".into()).message(
+            format!("Exported {} artifacts to {directory}", manifest.len()).as_str(),
+        );
+
+        Ok(())
+    }
+
+    /// Shows the files that contribute to the inputs digest of each target
+    /// and the files on disk that match its declared outputs.
+    pub fn show_files(
+        &self,
+        printer: &mut printer::Printer,
+        workspace: workspace::WorkspaceArc,
+        targets: &[Arc<str>],
+        show_files: ShowFiles,
+    ) -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct FileList {
+            count: usize,
+            files: Vec<Arc<str>>,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            missing: Vec<Arc<str>>,
+        }
+
+        #[derive(Serialize)]
+        struct RuleFiles {
+            rule: Arc<str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            inputs: Option<FileList>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            outputs: Option<FileList>,
+        }
+
+        let workspace_path = workspace.read().get_absolute_path();
+        let workspace_root = std::path::Path::new(workspace_path.as_ref());
+
+        let mut rule_files_list = Vec::new();
+        let mut multi_progress = printer::MultiProgress::new(printer);
+        let tasks = self.tasks.read();
+        for target in targets {
+            let task = tasks
+                .get(target)
+                .ok_or(format_error!("Task not found {target}"))?;
+
+            let inputs = if show_files.is_inputs {
+                let files = if let Some(inputs) = &task.rule.inputs {
+                    let mut progress = multi_progress.add_progress(target, None, None);
+                    let skip_folders = task.rule.get_skip_folders();
+                    workspace
+                        .write()
+                        .update_changes(&mut progress, inputs, skip_folders)
+                        .context(format_context!("Failed to update workspace changes"))?;
+                    workspace.read().inspect_rule_inputs(inputs, skip_folders)
+                } else {
+                    Vec::new()
+                };
+                Some(FileList {
+                    count: files.len(),
+                    files,
+                    missing: Vec::new(),
+                })
+            } else {
+                None
+            };
+
+            let outputs = if show_files.is_outputs {
+                let (files, missing) = if let Some(outputs) = &task.rule.outputs {
+                    let files: Vec<Arc<str>> = get_output_paths(workspace_root, outputs)
+                        .context(format_context!("Failed to resolve outputs for {target}"))?
+                        .into_iter()
+                        .filter(|path| path.is_file())
+                        .map(|path| {
+                            let relative = path.strip_prefix(workspace_root).unwrap_or(&path);
+                            relative.to_string_lossy().into()
+                        })
+                        .collect();
+                    let missing = get_missing_outputs(workspace_root, outputs)
+                        .context(format_context!("Failed to check outputs for {target}"))?;
+                    (files, missing)
+                } else {
+                    (Vec::new(), Vec::new())
+                };
+                Some(FileList {
+                    count: files.len(),
+                    files,
+                    missing,
+                })
+            } else {
+                None
+            };
+
+            rule_files_list.push(RuleFiles {
+                rule: target.clone(),
+                inputs,
+                outputs,
+            });
+        }
+        drop(multi_progress);
+
+        match show_files.format {
+            ListFormat::Yaml => printer.info("files", &rule_files_list)?,
+            ListFormat::Json => {
+                use std::io::Write;
+                let content = serde_json::to_string_pretty(&rule_files_list)
+                    .context(format_context!("Failed to serialize files"))?;
+                writeln!(std::io::stdout(), "{content}")
+                    .context(format_context!("Failed to write files"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the sorted tasks and the dependencies between them to stdout
+    /// as Graphviz DOT or JSON.
+    pub fn show_graph(&self, format: GraphFormat) -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct GraphNode {
+            rule: Arc<str>,
+            phase: Phase,
+            #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+            type_: Option<RuleType>,
+        }
+
+        #[derive(Serialize)]
+        struct GraphEdge {
+            rule: Arc<str>,
+            dep: Arc<str>,
+        }
+
+        #[derive(Serialize)]
+        struct TaskGraph {
+            nodes: Vec<GraphNode>,
+            edges: Vec<GraphEdge>,
+        }
+
+        let tasks = self.tasks.read();
+        let sorted: HashSet<petgraph::prelude::NodeIndex> = self.sorted.iter().copied().collect();
+        let mut task_graph = TaskGraph {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        };
+        for node_index in self.sorted.iter() {
+            let task_name = self.graph.get_task(*node_index);
+            let task = tasks
+                .get(task_name)
+                .ok_or(format_error!("Task not found {task_name}"))?;
+            task_graph.nodes.push(GraphNode {
+                rule: task.rule.name.clone(),
+                phase: task.phase,
+                type_: task.rule.type_,
+            });
+            // only edges within the sorted tasks so --target scopes the graph
+            for dep_index in self.graph.directed_graph.neighbors(*node_index) {
+                if sorted.contains(&dep_index) {
+                    task_graph.edges.push(GraphEdge {
+                        rule: task.rule.name.clone(),
+                        dep: self.graph.get_task(dep_index).into(),
+                    });
+                }
+            }
+        }
+
+        let content = match format {
+            GraphFormat::Json => serde_json::to_string_pretty(&task_graph)
+                .context(format_context!("Failed to serialize graph"))?,
+            GraphFormat::Dot => {
+                let mut lines = vec!["digraph spaces {".to_string()];
+                for node in task_graph.nodes.iter() {
+                    let type_ = node
+                        .type_
+                        .map(|type_| format!(" {type_:?}"))
+                        .unwrap_or_default();
+                    lines.push(format!(
+                        "  \"{}\" [label=\"{}\\n{:?}{type_}\"];",
+                        node.rule, node.rule, node.phase
+                    ));
+                }
+                for edge in task_graph.edges.iter() {
+                    lines.push(format!("  \"{}\" -> \"{}\";", edge.rule, edge.dep));
+                }
+                lines.push("}".to_string());
+                lines.join("\n")
+            }
+        };
+
+        use std::io::Write;
+        writeln!(std::io::stdout(), "{content}").context(format_context!("Failed to write graph"))?;
+        Ok(())
+    }
+
+    pub fn show_checkout_plan(&self, printer: &mut printer::Printer) -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct PlanEntry<'a> {
+            rule: &'a str,
+            phase: Phase,
+            task: &'a executor::Task,
+        }
+
+        let tasks = self.tasks.read();
+        let mut plan = Vec::new();
+        for node_index in self.sorted.iter() {
+            let task_name = self.graph.get_task(*node_index);
+            let task = tasks
+                .get(task_name)
+                .ok_or(format_error!("Task not found {task_name}"))?;
+            if task.phase == Phase::Checkout || task.phase == Phase::PostCheckout {
+                plan.push(PlanEntry {
+                    rule: task.rule.name.as_ref(),
+                    phase: task.phase,
+                    task: &task.executor,
+                });
+            }
+        }
+
+        printer.info("checkout plan", &plan)?;
+        Ok(())
+    }
+
+    /// Applies the same skip checks as Task::execute without executing
+    /// anything or saving any digests.
+    pub fn dry_run(
+        &self,
+        printer: &mut printer::Printer,
+        workspace: workspace::WorkspaceArc,
+        phase: Phase,
+    ) -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct PlanEntry {
+            rule: Arc<str>,
+            status: String,
+        }
+
+        let mut plan = Vec::new();
+        let mut will_execute = HashSet::new();
+        let tasks = self.tasks.read();
+        let mut multi_progress = printer::MultiProgress::new(printer);
+
+        for node_index in self.sorted.iter() {
+            let task_name = self.graph.get_task(*node_index);
+            let task = tasks
+                .get(task_name)
+                .ok_or(format_error!("Task not found {task_name}"))?;
+
+            if task.phase != phase {
+                continue;
+            }
+
+            let is_dependency_pending = task
+                .rule
+                .deps
+                .as_ref()
+                .map(|deps| deps.iter().any(|dep| will_execute.contains(dep)))
+                .unwrap_or(false);
+
+            let mut progress = multi_progress.add_progress(task_name, None, None);
+            let status = match get_run_decision(
+                &mut progress,
+                workspace.clone(),
+                &task.rule,
+                &task.executor,
+                is_dependency_pending,
+            )? {
+                RunDecision::Run { reason, .. } => format!("run: {reason}"),
+                RunDecision::Skip { reason } => format!("skip: {reason}"),
+            };
+
+            if status.starts_with("run") {
+                will_execute.insert(task.rule.name.clone());
+            }
+
+            plan.push(PlanEntry {
+                rule: task.rule.name.clone(),
+                status,
+            });
+        }
+
+        drop(multi_progress);
+        printer.info("plan", &plan)?;
+
+        Ok(())
+    }
+
     pub fn execute(
         &self,
         printer: &mut printer::Printer,
@@ -550,10 +1694,34 @@ impl State {
         phase: Phase,
     ) -> anyhow::Result<executor::TaskResult> {
         let mut task_result = executor::TaskResult::new();
+
+        // rules ordered after --until are not staged, nothing before it depends on them
+        let run_until = if phase == Phase::Run {
+            singleton::get_run_until()
+        } else {
+            None
+        };
+        let sorted = if let Some(run_until) = run_until.as_ref() {
+            let until_index = self
+                .sorted
+                .iter()
+                .position(|node_index| self.graph.get_task(*node_index) == run_until.as_ref())
+                .ok_or(format_error!("Rule passed to --until not found: {run_until}"))?;
+            for node_index in self.sorted[until_index + 1..].iter() {
+                logger::Logger::new_printer(printer, "until".into()).debug(
+                    format!("Skipping {} (after {run_until})", self.graph.get_task(*node_index))
+                        .as_str(),
+                );
+            }
+            &self.sorted[..=until_index]
+        } else {
+            self.sorted.as_slice()
+        };
+
         let mut multi_progress = printer::MultiProgress::new(printer);
         let mut handle_list = Vec::new();
 
-        for node_index in self.sorted.iter() {
+        for node_index in sorted.iter() {
             let task_name = self.graph.get_task(*node_index);
             let task = {
                 let tasks = self.tasks.read();
@@ -678,3 +1846,142 @@ pub fn get_path_to_build_checkout(rule_name: Arc<str>) -> anyhow::Result<Arc<str
     let rule_name = state.get_sanitized_rule_name(rule_name);
     Ok(format!("build/{}", rule_name).into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outputs_digest_tracks_modified_and_deleted_files() {
+        let root = std::env::temp_dir().join(format!("spaces-outputs-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("build")).unwrap();
+        std::fs::write(root.join("build/app"), "v1").unwrap();
+        std::fs::write(root.join("build/app.log"), "log").unwrap();
+        let outputs: HashSet<Arc<str>> = HashSet::from(["build/app".into()]);
+
+        let digest = get_outputs_digest(&root, &outputs).unwrap();
+        assert_eq!(get_outputs_digest(&root, &outputs).unwrap(), digest);
+
+        // files that are not outputs don't change the digest
+        std::fs::write(root.join("build/app.log"), "more log").unwrap();
+        assert_eq!(get_outputs_digest(&root, &outputs).unwrap(), digest);
+
+        std::fs::write(root.join("build/app"), "v2").unwrap();
+        let modified = get_outputs_digest(&root, &outputs).unwrap();
+        assert_ne!(modified, digest);
+
+        std::fs::remove_file(root.join("build/app")).unwrap();
+        let deleted = get_outputs_digest(&root, &outputs).unwrap();
+        assert_ne!(deleted, digest);
+        assert_ne!(deleted, modified);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn json_differences_list_changed_paths() {
+        let existing = serde_json::json!({
+            "rule": {"name": "//:sdk", "deps": ["//:tools"]},
+            "executor": {"Git": {"url": "https://example.com/sdk", "checkout": {"Revision": "v1"}}},
+        });
+        let new = serde_json::json!({
+            "rule": {"name": "//:sdk", "deps": ["//:other"]},
+            "executor": {"Git": {"url": "https://example.com/sdk", "checkout": {"Revision": "v2"}}},
+            "extra": true,
+        });
+
+        let mut differences = Vec::new();
+        get_json_differences("", &existing, &existing, &mut differences);
+        assert!(differences.is_empty());
+
+        get_json_differences("", &existing, &new, &mut differences);
+        assert_eq!(
+            differences,
+            vec![
+                "  executor.Git.checkout.Revision: \"v1\" -> \"v2\"".to_string(),
+                "  extra: null -> true".to_string(),
+                "  rule.deps: [\"//:tools\"] -> [\"//:other\"]".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat_restores_phase_and_type() {
+        let rule = Rule {
+            name: "//:test".into(),
+            deps: None,
+            description: None,
+            help: None,
+            inputs: None,
+            outputs: None,
+            skip_folders: None,
+            artifacts: None,
+            platforms: None,
+            type_: Some(RuleType::Optional),
+            resource: None,
+        };
+        let mut tasks: HashMap<Arc<str>, Task> = HashMap::new();
+        tasks.insert(rule.name.clone(), Task::new(rule, Phase::Run, executor::Task::Target));
+        let states = get_task_states(&tasks);
+
+        // an earlier run enabled the optional rule and cancelled it
+        let task = tasks.get_mut("//:test").unwrap();
+        task.rule.type_ = Some(RuleType::Run);
+        task.phase = Phase::Cancelled;
+
+        restore_task_states(&mut tasks, &states);
+        let task = tasks.get("//:test").unwrap();
+        assert_eq!(task.rule.type_, Some(RuleType::Optional));
+        assert_eq!(task.phase, Phase::Run);
+    }
+
+    #[test]
+    fn clean_paths_must_be_inside_workspace() {
+        let root = std::env::temp_dir().join(format!("spaces-clean-{}", std::process::id()));
+        let workspace_root = root.join("workspace");
+        std::fs::create_dir_all(workspace_root.join("build/rule")).unwrap();
+        std::fs::write(root.join("outside.txt"), "keep").unwrap();
+        let workspace_root = workspace_root.canonicalize().unwrap();
+
+        let inside = workspace_root.join("build/rule");
+        let missing = workspace_root.join("build/missing");
+        let paths = get_clean_paths(&workspace_root, vec![inside.clone(), missing]).unwrap();
+        assert_eq!(paths, vec![inside.clone()]);
+
+        // one escaping path rejects the whole set
+        let escaping = workspace_root.join("../outside.txt");
+        assert!(get_clean_paths(&workspace_root, vec![inside.clone(), escaping]).is_err());
+        assert!(get_clean_paths(&workspace_root, vec![workspace_root.clone()]).is_err());
+        assert!(inside.exists());
+        assert!(root.join("outside.txt").exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn wrap_long_help_to_width() {
+        let help = "Builds the project with the release profile and copies the resulting binaries into the sysroot so later rules can use them";
+        let wrapped = wrap_text(help, 40);
+        for line in wrapped.lines() {
+            assert!(line.len() <= 40 - HELP_INDENT, "line too long: {line}");
+        }
+        assert_eq!(wrapped.split_whitespace().collect::<Vec<_>>().join(" "), help);
+        assert_eq!(wrap_text("short help", 40).as_ref(), "short help");
+        assert_eq!(wrap_text("first\nsecond", 40).as_ref(), "first\nsecond");
+    }
+
+    #[test]
+    fn expand_glob_deps_to_matching_rules() {
+        let rule_names: Vec<Arc<str>> =
+            ["tools/b:build", "tools/a:build", "tools/a:test", "app:build"]
+                .into_iter()
+                .map(Arc::from)
+                .collect();
+        let deps: Vec<Arc<str>> = vec!["tools/*:build".into(), "tools/a:build".into()];
+        let expanded = get_expanded_deps("app:build", &deps, &rule_names).unwrap();
+        assert_eq!(expanded, vec![Arc::from("tools/a:build"), Arc::from("tools/b:build")]);
+
+        let deps: Vec<Arc<str>> = vec!["missing/*:build".into()];
+        assert!(get_expanded_deps("app:build", &deps, &rule_names).is_err());
+    }
+}
@@ -1,10 +1,10 @@
-use crate::{evaluator, rules, workspace};
+use crate::{evaluator, rules, singleton, summary, workspace};
 use anyhow::Context;
-use anyhow_source_location::format_context;
+use anyhow_source_location::{format_context, format_error};
 use std::sync::Arc;
 
 pub enum RunWorkspace {
-    Target(Option<Arc<str>>),
+    Target(Vec<Arc<str>>),
     Script(Vec<(Arc<str>, Arc<str>)>),
 }
 
@@ -25,10 +25,29 @@ pub fn run_starlark_modules_in_workspace(
 
     let workspace_arc = workspace::WorkspaceArc::new(lock::StateLock::new(workspace));
     match run_workspace {
-        RunWorkspace::Target(target) => {
+        RunWorkspace::Target(targets) => {
             let modules = workspace_arc.read().modules.clone();
-            evaluator::run_starlark_modules(printer, workspace_arc.clone(), modules, phase, target)
-                .context(format_context!("while executing workspace rules"))?
+            let run_result = evaluator::run_starlark_modules(
+                printer,
+                workspace_arc.clone(),
+                modules,
+                phase,
+                targets,
+            );
+
+            // the summary is written even if the run fails so CI can report the failures
+            if phase == rules::Phase::Run {
+                if let Some(run_summary) = singleton::get_summary() {
+                    summary::write(workspace_arc.clone(), &run_summary)
+                        .context(format_context!("while writing run summary"))?;
+                }
+                if let Some(count) = singleton::get_report_slow() {
+                    summary::show_slow_rules(printer, &workspace_arc.read(), count)
+                        .context(format_context!("while reporting the slowest rules"))?;
+                }
+            }
+
+            run_result.context(format_context!("while executing workspace rules"))?
         }
         RunWorkspace::Script(scripts) => {
             for (name, _) in scripts.iter() {
@@ -38,9 +57,17 @@ pub fn run_starlark_modules_in_workspace(
             workspace_arc.write().is_create_lock_file = is_create_lock_file;
             workspace_arc.write().digest = workspace::calculate_digest(&scripts);
 
-            evaluator::run_starlark_modules(printer, workspace_arc.clone(), scripts, phase, None)
+            evaluator::run_starlark_modules(printer, workspace_arc.clone(), scripts, phase, Vec::new())
                 .context(format_context!("while evaulating starlark modules"))?;
 
+            let lock_drift = workspace_arc.read().lock_drift.clone();
+            if !lock_drift.is_empty() {
+                return Err(format_error!(
+                    "--frozen checkout does not match the lock file:\n{}",
+                    lock_drift.join("\n")
+                ));
+            }
+
             workspace_arc
                 .read()
                 .save_lock_file()
@@ -48,18 +75,50 @@ pub fn run_starlark_modules_in_workspace(
         }
     }
 
-    workspace::RuleMetricsFile::update(workspace_arc.clone())
-        .context(format_context!("Failed to update rule metrics file"))?;
+    // only checkout and run execute rules, a dry run or a printed plan doesn't execute any
+    let is_rules_executed = matches!(phase, rules::Phase::Checkout | rules::Phase::Run)
+        && !singleton::get_is_dry_run()
+        && !singleton::get_is_print_plan();
+    if is_rules_executed {
+        workspace::RuleMetricsFile::update(workspace_arc.clone())
+            .context(format_context!("Failed to update rule metrics file"))?;
+    }
+
+    lock::report_slow_acquisitions(printer);
 
     Ok(())
 }
 
+fn get_checkout_script_path(script: &str) -> Arc<str> {
+    if workspace::is_rules_module(script) {
+        script.into()
+    } else {
+        format!("{script}.{}", workspace::SPACES_MODULE_NAME).into()
+    }
+}
+
+pub fn is_checkout_script_present(script: &str) -> bool {
+    std::path::Path::new(get_checkout_script_path(script).as_ref()).exists()
+}
+
 pub fn checkout(
     printer: &mut printer::Printer,
     name: Arc<str>,
     script: Vec<Arc<str>>,
     create_lock_file: bool,
 ) -> anyhow::Result<()> {
+    // printing the plan evaluates the scripts in a scratch workspace that is removed afterwards
+    let is_print_plan = singleton::get_is_print_plan();
+    let name: Arc<str> = if is_print_plan {
+        std::env::temp_dir()
+            .join(format!("spaces-plan-{}", std::process::id()))
+            .join(name.as_ref())
+            .to_string_lossy()
+            .into()
+    } else {
+        name
+    };
+
     std::fs::create_dir_all(name.as_ref())
         .context(format_context!("while creating workspace directory {name}"))?;
 
@@ -67,11 +126,7 @@ pub fn checkout(
     let mut scripts = Vec::new();
 
     for one_script in script {
-        let script_path = if workspace::is_rules_module(&one_script) {
-            one_script.clone()
-        } else {
-            format!("{one_script}.{}", workspace::SPACES_MODULE_NAME).into()
-        };
+        let script_path = get_checkout_script_path(one_script.as_ref());
 
         let script_as_path = std::path::Path::new(script_path.as_ref());
         let file_name: Arc<str> = script_as_path.file_name().unwrap().to_string_lossy().into();
@@ -88,6 +143,7 @@ pub fn checkout(
     }
 
     settings.store_path = workspace::get_checkout_store_path();
+    settings.spaces_version = Some(env!("CARGO_PKG_VERSION").into());
 
     std::fs::write(format!("{}/{}", name, workspace::ENV_FILE_NAME), "").context(
         format_context!("while creating {} file", workspace::ENV_FILE_NAME),
@@ -99,7 +155,7 @@ pub fn checkout(
     let target_workspace_directory = current_working_directory.join(name.as_ref());
     let absolute_path_to_workspace: Arc<str> = target_workspace_directory.to_string_lossy().into();
 
-    run_starlark_modules_in_workspace(
+    let checkout_result = run_starlark_modules_in_workspace(
         printer,
         rules::Phase::Checkout,
         Some(absolute_path_to_workspace.clone()),
@@ -108,7 +164,16 @@ pub fn checkout(
     )
     .context(format_context!(
         "while evaulating starklark modules for checkout"
-    ))?;
+    ));
+
+    if is_print_plan {
+        // the scratch workspace is removed even if the plan could not be evaluated
+        if let Some(scratch_directory) = std::path::Path::new(name.as_ref()).parent() {
+            let _ = std::fs::remove_dir_all(scratch_directory);
+        }
+        return checkout_result;
+    }
+    checkout_result?;
 
     settings
         .save(absolute_path_to_workspace.as_ref())
@@ -116,3 +181,87 @@ pub fn checkout(
 
     Ok(())
 }
+
+/// Promotes a shallow member repository of the workspace to its full history
+pub fn unshallow(printer: &mut printer::Printer, repo: Arc<str>) -> anyhow::Result<()> {
+    let current_working_directory = std::env::current_dir()
+        .context(format_context!("Failed to get current working directory"))?;
+    let workspace_root = workspace::Workspace::find_workspace_root(
+        current_working_directory.to_string_lossy().as_ref(),
+    )
+    .context(format_context!("unshallow must run inside a workspace"))?;
+
+    let full_path: Arc<str> = format!("{workspace_root}/{repo}").into();
+    if !std::path::Path::new(full_path.as_ref()).join(".git").exists() {
+        return Err(format_error!("{repo} is not a git repository in {workspace_root}"));
+    }
+
+    let mut multi_progress = printer::MultiProgress::new(printer);
+    let mut progress = multi_progress.add_progress(repo.as_ref(), None, Some("Complete"));
+    git::Repository::new(repo.clone(), full_path)
+        .unshallow(&mut progress)
+        .context(format_context!("while promoting {repo} to full depth"))?;
+
+    Ok(())
+}
+
+/// Shows the rule metrics of the last run sorted by duration (longest first)
+pub fn show_metrics(printer: &mut printer::Printer) -> anyhow::Result<()> {
+    #[derive(serde::Serialize)]
+    struct MetricsEntry {
+        rule: Arc<str>,
+        phase: Option<rules::Phase>,
+        duration_ms: u128,
+        was_skipped: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        skip_reason: Option<Arc<str>>,
+    }
+
+    #[derive(serde::Serialize)]
+    struct MetricsSummary {
+        date: Arc<str>,
+        total_duration_ms: u128,
+        rules: Vec<MetricsEntry>,
+    }
+
+    let current_working_directory = std::env::current_dir()
+        .context(format_context!("Failed to get current working directory"))?;
+    let workspace_root = workspace::Workspace::find_workspace_root(
+        current_working_directory.to_string_lossy().as_ref(),
+    )
+    .context(format_context!("metrics must run inside a workspace"))?;
+
+    let metrics_file = workspace::RuleMetricsFile::load(workspace_root.as_ref())
+        .context(format_context!("Failed to load the workspace metrics"))?;
+    let last_run = metrics_file
+        .runs
+        .last()
+        .ok_or(format_error!("No metrics have been recorded in {workspace_root}"))?;
+
+    let mut rules: Vec<MetricsEntry> = last_run
+        .rules
+        .iter()
+        .map(|(rule, metrics)| MetricsEntry {
+            rule: rule.clone(),
+            phase: metrics.phase,
+            duration_ms: metrics.duration_ms,
+            was_skipped: metrics.was_skipped,
+            skip_reason: metrics.skip_reason.clone(),
+        })
+        .collect();
+    rules.sort_by(|first, second| {
+        second
+            .duration_ms
+            .cmp(&first.duration_ms)
+            .then_with(|| first.rule.cmp(&second.rule))
+    });
+
+    let summary = MetricsSummary {
+        date: last_run.date.clone(),
+        total_duration_ms: last_run.total_duration_ms,
+        rules,
+    };
+    printer.info("metrics", &summary)?;
+
+    Ok(())
+}
@@ -1,5 +1,7 @@
-use crate::workspace;
+use crate::{executor, rules, summary, workspace};
 use anyhow_source_location::format_error;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 
 
@@ -7,8 +9,32 @@ use anyhow_source_location::format_error;
 struct State {
     active_workspace: Option<workspace::WorkspaceArc>,
     is_ci: bool,
+    is_strict: bool,
+    is_offline: bool,
+    is_dry_run: bool,
+    is_print_plan: bool,
+    is_frozen: bool,
+    is_include_optional: bool,
+    is_ignore_inputs: bool,
+    is_isolate_tmp: bool,
+    is_keep_tmp_on_failure: bool,
+    dirty_policy: git::DirtyPolicy,
+    pre_run_hook: Option<executor::exec::Exec>,
+    post_run_hook: Option<executor::exec::Exec>,
+    show_files: Option<rules::ShowFiles>,
+    show_graph: Option<rules::GraphFormat>,
+    list_format: rules::ListFormat,
+    run_until: Option<Arc<str>>,
     max_queue_count: i64,
-    error_chain: Vec<String>
+    jobs: Option<i64>,
+    repeat: i64,
+    error_chain: Vec<String>,
+    summary: Option<summary::Summary>,
+    report_slow: Option<usize>,
+    store_path: Option<Arc<str>>,
+    allow_gh: bool,
+    export_artifacts: Option<Arc<str>>,
+    resource_capacities: HashMap<Arc<str>, i64>,
 }
 
 static STATE: state::InitCell<lock::StateLock<State>> = state::InitCell::new();
@@ -19,9 +45,36 @@ fn get_state() -> &'static lock::StateLock<State> {
     }
     STATE.set(lock::StateLock::new(State {
         is_ci: false,
-        max_queue_count: 8,
+        is_strict: false,
+        is_offline: false,
+        is_dry_run: false,
+        is_print_plan: false,
+        is_frozen: false,
+        is_include_optional: false,
+        is_ignore_inputs: false,
+        is_isolate_tmp: false,
+        is_keep_tmp_on_failure: false,
+        dirty_policy: git::DirtyPolicy::Fail,
+        pre_run_hook: None,
+        post_run_hook: None,
+        show_files: None,
+        show_graph: None,
+        list_format: rules::ListFormat::Yaml,
+        run_until: None,
+        // the number of logical CPUs
+        max_queue_count: std::thread::available_parallelism()
+            .map(|count| count.get() as i64)
+            .unwrap_or(8),
+        jobs: None,
+        repeat: 1,
         active_workspace: None,
-        error_chain: Vec::new()
+        error_chain: Vec::new(),
+        summary: None,
+        report_slow: None,
+        store_path: None,
+        allow_gh: true,
+        export_artifacts: None,
+        resource_capacities: HashMap::new(),
     }));
 
     STATE.get()
@@ -53,7 +106,7 @@ pub fn show_error_chain() {
 
 pub fn get_max_queue_count() -> i64 {
     let state = get_state().read();
-    state.max_queue_count
+    state.jobs.unwrap_or(state.max_queue_count)
 }
 
 pub fn set_max_queue_count(max_queue_count: i64) {
@@ -61,6 +114,22 @@ pub fn set_max_queue_count(max_queue_count: i64) {
     state.max_queue_count = max_queue_count;
 }
 
+/// `-j/--jobs` from the command line takes precedence over `info.set_max_queue_count()`
+pub fn set_jobs(jobs: Option<i64>) {
+    let mut state = get_state().write();
+    state.jobs = jobs;
+}
+
+pub fn get_resource_capacity(resource: &str) -> i64 {
+    let state = get_state().read();
+    state.resource_capacities.get(resource).copied().unwrap_or(1)
+}
+
+pub fn set_resource_capacity(resource: Arc<str>, capacity: i64) {
+    let mut state = get_state().write();
+    state.resource_capacities.insert(resource, capacity);
+}
+
 pub fn get_is_ci() -> bool {
     let state = get_state().read();
     state.is_ci
@@ -71,6 +140,225 @@ pub fn set_ci(is_ci: bool) {
     state.is_ci = is_ci;
 }
 
+pub fn get_summary() -> Option<summary::Summary> {
+    let state = get_state().read();
+    state.summary.clone()
+}
+
+pub fn set_summary(summary: Option<summary::Summary>) {
+    let mut state = get_state().write();
+    state.summary = summary;
+}
+
+pub fn get_report_slow() -> Option<usize> {
+    let state = get_state().read();
+    state.report_slow
+}
+
+pub fn set_report_slow(count: Option<usize>) {
+    let mut state = get_state().write();
+    state.report_slow = count;
+}
+
+pub fn get_is_dry_run() -> bool {
+    let state = get_state().read();
+    state.is_dry_run
+}
+
+pub fn set_dry_run(is_dry_run: bool) {
+    let mut state = get_state().write();
+    state.is_dry_run = is_dry_run;
+}
+
+pub fn get_is_print_plan() -> bool {
+    let state = get_state().read();
+    state.is_print_plan
+}
+
+pub fn set_print_plan(is_print_plan: bool) {
+    let mut state = get_state().write();
+    state.is_print_plan = is_print_plan;
+}
+
+pub fn get_is_frozen() -> bool {
+    let state = get_state().read();
+    state.is_frozen
+}
+
+pub fn set_frozen(is_frozen: bool) {
+    let mut state = get_state().write();
+    state.is_frozen = is_frozen;
+}
+
+pub fn get_is_include_optional() -> bool {
+    let state = get_state().read();
+    state.is_include_optional
+}
+
+pub fn set_include_optional(is_include_optional: bool) {
+    let mut state = get_state().write();
+    state.is_include_optional = is_include_optional;
+}
+
+pub fn get_is_ignore_inputs() -> bool {
+    let state = get_state().read();
+    state.is_ignore_inputs
+}
+
+pub fn set_ignore_inputs(is_ignore_inputs: bool) {
+    let mut state = get_state().write();
+    state.is_ignore_inputs = is_ignore_inputs;
+}
+
+pub fn get_is_isolate_tmp() -> bool {
+    let state = get_state().read();
+    state.is_isolate_tmp
+}
+
+pub fn set_isolate_tmp(is_isolate_tmp: bool) {
+    let mut state = get_state().write();
+    state.is_isolate_tmp = is_isolate_tmp;
+}
+
+pub fn get_is_keep_tmp_on_failure() -> bool {
+    let state = get_state().read();
+    state.is_keep_tmp_on_failure
+}
+
+pub fn set_keep_tmp_on_failure(is_keep_tmp_on_failure: bool) {
+    let mut state = get_state().write();
+    state.is_keep_tmp_on_failure = is_keep_tmp_on_failure;
+}
+
+pub fn get_is_strict() -> bool {
+    let state = get_state().read();
+    state.is_strict
+}
+
+pub fn set_strict(is_strict: bool) {
+    let mut state = get_state().write();
+    state.is_strict = is_strict;
+}
+
+pub fn get_is_offline() -> bool {
+    let state = get_state().read();
+    state.is_offline
+}
+
+pub fn set_offline(is_offline: bool) {
+    let mut state = get_state().write();
+    state.is_offline = is_offline;
+}
+
+pub fn get_dirty_policy() -> git::DirtyPolicy {
+    let state = get_state().read();
+    state.dirty_policy
+}
+
+pub fn set_dirty_policy(dirty_policy: git::DirtyPolicy) {
+    let mut state = get_state().write();
+    state.dirty_policy = dirty_policy;
+}
+
+pub fn set_run_hooks(
+    pre_run_hook: Option<executor::exec::Exec>,
+    post_run_hook: Option<executor::exec::Exec>,
+) {
+    let mut state = get_state().write();
+    state.pre_run_hook = pre_run_hook;
+    state.post_run_hook = post_run_hook;
+}
+
+pub fn get_pre_run_hook() -> Option<executor::exec::Exec> {
+    let state = get_state().read();
+    state.pre_run_hook.clone()
+}
+
+pub fn get_post_run_hook() -> Option<executor::exec::Exec> {
+    let state = get_state().read();
+    state.post_run_hook.clone()
+}
+
+pub fn get_repeat() -> i64 {
+    let state = get_state().read();
+    state.repeat
+}
+
+pub fn set_repeat(repeat: i64) {
+    let mut state = get_state().write();
+    state.repeat = repeat;
+}
+
+pub fn get_show_files() -> Option<rules::ShowFiles> {
+    let state = get_state().read();
+    state.show_files
+}
+
+pub fn set_show_files(show_files: Option<rules::ShowFiles>) {
+    let mut state = get_state().write();
+    state.show_files = show_files;
+}
+
+pub fn get_show_graph() -> Option<rules::GraphFormat> {
+    let state = get_state().read();
+    state.show_graph
+}
+
+pub fn set_show_graph(show_graph: Option<rules::GraphFormat>) {
+    let mut state = get_state().write();
+    state.show_graph = show_graph;
+}
+
+pub fn get_list_format() -> rules::ListFormat {
+    let state = get_state().read();
+    state.list_format
+}
+
+pub fn set_list_format(list_format: rules::ListFormat) {
+    let mut state = get_state().write();
+    state.list_format = list_format;
+}
+
+pub fn get_run_until() -> Option<Arc<str>> {
+    let state = get_state().read();
+    state.run_until.clone()
+}
+
+pub fn set_run_until(run_until: Option<Arc<str>>) {
+    let mut state = get_state().write();
+    state.run_until = run_until;
+}
+
+pub fn get_export_artifacts() -> Option<Arc<str>> {
+    let state = get_state().read();
+    state.export_artifacts.clone()
+}
+
+pub fn set_export_artifacts(directory: Option<Arc<str>>) {
+    let mut state = get_state().write();
+    state.export_artifacts = directory;
+}
+
+pub fn get_store_path() -> Option<Arc<str>> {
+    let state = get_state().read();
+    state.store_path.clone()
+}
+
+pub fn set_store_path(store_path: Option<Arc<str>>) {
+    let mut state = get_state().write();
+    state.store_path = store_path;
+}
+
+pub fn get_allow_gh() -> bool {
+    let state = get_state().read();
+    state.allow_gh
+}
+
+pub fn set_allow_gh(value: bool) {
+    let mut state = get_state().write();
+    state.allow_gh = value;
+}
+
 pub fn set_active_workspace(workspace: workspace::WorkspaceArc) {
     let mut state = get_state().write();
     state.active_workspace = Some(workspace);
@@ -0,0 +1,229 @@
+use crate::workspace;
+use anyhow::Context;
+use anyhow_source_location::format_context;
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const LOG_TAIL_LINES: usize = 50;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SummaryFormat {
+    Junit,
+}
+
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub format: SummaryFormat,
+    pub output: Arc<str>,
+}
+
+fn escape_xml(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    for character in input.chars() {
+        match character {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            // control characters are not allowed in XML 1.0
+            c if c.is_control() && c != '\n' && c != '\t' && c != '\r' => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn get_log_tail(log_file: &str) -> String {
+    let content = std::fs::read_to_string(log_file).unwrap_or_default();
+    let lines: Vec<&str> = content.lines().collect();
+    let start = lines.len().saturating_sub(LOG_TAIL_LINES);
+    lines[start..].join("\n")
+}
+
+fn create_junit(
+    rule_metrics: &HashMap<Arc<str>, workspace::RuleMetrics>,
+    get_log_file: impl Fn(&str) -> Arc<str>,
+) -> String {
+    let mut rule_names: Vec<&Arc<str>> = rule_metrics.keys().collect();
+    rule_names.sort();
+
+    let mut failures = 0;
+    let mut skipped = 0;
+    let mut total_time = 0.0;
+    let mut test_cases = String::new();
+
+    for rule_name in rule_names {
+        let metrics = &rule_metrics[rule_name];
+        total_time += metrics.elapsed_time;
+
+        let name = escape_xml(rule_name);
+        let time = format!("{:.3}", metrics.elapsed_time);
+        match metrics.status {
+            workspace::RuleStatus::Success => {
+                test_cases.push_str(&format!(
+                    "    <testcase name=\"{name}\" classname=\"spaces\" time=\"{time}\"/>\n"
+                ));
+            }
+            workspace::RuleStatus::Skipped => {
+                skipped += 1;
+                test_cases.push_str(&format!(
+                    "    <testcase name=\"{name}\" classname=\"spaces\" time=\"{time}\">\n      <skipped/>\n    </testcase>\n"
+                ));
+            }
+            workspace::RuleStatus::Failure => {
+                failures += 1;
+                let log_file = get_log_file(rule_name);
+                let log_tail = escape_xml(&get_log_tail(&log_file));
+                test_cases.push_str(&format!(
+                    "    <testcase name=\"{name}\" classname=\"spaces\" time=\"{time}\">\n      <failure message=\"rule failed, see {}\">{log_tail}</failure>\n    </testcase>\n",
+                    escape_xml(&log_file)
+                ));
+            }
+        }
+    }
+
+    let tests = rule_metrics.len();
+    let mut result = String::new();
+    result.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    result.push_str(&format!(
+        "<testsuites name=\"spaces\" tests=\"{tests}\" failures=\"{failures}\" skipped=\"{skipped}\" time=\"{total_time:.3}\">\n"
+    ));
+    result.push_str(&format!(
+        "  <testsuite name=\"spaces run\" tests=\"{tests}\" failures=\"{failures}\" skipped=\"{skipped}\" time=\"{total_time:.3}\">\n"
+    ));
+    result.push_str(&test_cases);
+    result.push_str("  </testsuite>\n</testsuites>\n");
+    result
+}
+
+/// Returns the names and durations of the `count` slowest rules that executed, slowest first.
+fn get_slow_rules(
+    rule_metrics: &HashMap<Arc<str>, workspace::RuleMetrics>,
+    count: usize,
+) -> Vec<(Arc<str>, u128)> {
+    let mut rules: Vec<(Arc<str>, u128)> = rule_metrics
+        .iter()
+        .filter(|(_, metrics)| metrics.status != workspace::RuleStatus::Skipped)
+        .map(|(name, metrics)| (name.clone(), metrics.duration_ms))
+        .collect();
+    rules.sort_by(|first, second| second.1.cmp(&first.1).then_with(|| first.0.cmp(&second.0)));
+    rules.truncate(count);
+    rules
+}
+
+pub fn show_slow_rules(
+    printer: &mut printer::Printer,
+    workspace: &workspace::Workspace,
+    count: usize,
+) -> anyhow::Result<()> {
+    #[derive(serde::Serialize)]
+    struct SlowRule {
+        rule: Arc<str>,
+        duration_ms: u128,
+    }
+
+    let slow_rules: Vec<SlowRule> = get_slow_rules(&workspace.rule_metrics, count)
+        .into_iter()
+        .map(|(rule, duration_ms)| SlowRule { rule, duration_ms })
+        .collect();
+    printer.info("slowest rules", &slow_rules)?;
+    Ok(())
+}
+
+pub fn write(workspace: workspace::WorkspaceArc, summary: &Summary) -> anyhow::Result<()> {
+    let content = match summary.format {
+        SummaryFormat::Junit => {
+            let workspace = workspace.read();
+            create_junit(&workspace.rule_metrics, |rule_name| {
+                workspace.get_log_file(rule_name)
+            })
+        }
+    };
+
+    let output_path = std::path::Path::new(summary.output.as_ref());
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .context(format_context!("Failed to create directory {parent:?}"))?;
+    }
+
+    std::fs::write(output_path, content)
+        .context(format_context!("Failed to write summary to {}", summary.output))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_metrics(duration_ms: u128, status: workspace::RuleStatus) -> workspace::RuleMetrics {
+        workspace::RuleMetrics {
+            elapsed_time: duration_ms as f64 / 1000.0,
+            status,
+            duration_ms,
+            was_skipped: status == workspace::RuleStatus::Skipped,
+            skip_reason: None,
+            phase: None,
+        }
+    }
+
+    #[test]
+    fn slow_rules_skip_skipped_rules_and_sort_by_duration() {
+        use workspace::RuleStatus::{Failure, Skipped, Success};
+        let rule_metrics: HashMap<Arc<str>, workspace::RuleMetrics> = [
+            ("//:a", new_metrics(10, Success)),
+            ("//:b", new_metrics(30, Failure)),
+            ("//:c", new_metrics(10, Success)),
+            ("//:d", new_metrics(100, Skipped)),
+            ("//:e", new_metrics(5, Success)),
+        ]
+        .into_iter()
+        .map(|(name, metrics)| (name.into(), metrics))
+        .collect();
+
+        let slow_rules = get_slow_rules(&rule_metrics, 3);
+        let slow_rules: Vec<(&str, u128)> = slow_rules
+            .iter()
+            .map(|(name, duration_ms)| (name.as_ref(), *duration_ms))
+            .collect();
+        // ties are broken by name
+        assert_eq!(slow_rules, vec![("//:b", 30), ("//:a", 10), ("//:c", 10)]);
+        assert_eq!(get_slow_rules(&rule_metrics, 10).len(), 4);
+    }
+
+    #[test]
+    fn junit_reports_passed_failed_and_skipped_rules() {
+        use workspace::RuleStatus::{Failure, Skipped, Success};
+        let root = std::env::temp_dir().join(format!("spaces_junit_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let log_file: Arc<str> = root.join("fail&.log").to_string_lossy().into();
+        std::fs::write(log_file.as_ref(), "building\nerror: a < b & \"c\"\n").unwrap();
+
+        let rule_metrics: HashMap<Arc<str>, workspace::RuleMetrics> = [
+            ("//:pass", new_metrics(1500, Success)),
+            ("//:fail<1>", new_metrics(250, Failure)),
+            ("//:skip&", new_metrics(0, Skipped)),
+        ]
+        .into_iter()
+        .map(|(name, metrics)| (name.into(), metrics))
+        .collect();
+
+        let junit = create_junit(&rule_metrics, |_| log_file.clone());
+        assert!(junit.contains(
+            "<testsuites name=\"spaces\" tests=\"3\" failures=\"1\" skipped=\"1\" time=\"1.750\">"
+        ));
+        assert!(junit.contains("<testcase name=\"//:pass\" classname=\"spaces\" time=\"1.500\"/>"));
+        assert!(junit.contains(
+            "<testcase name=\"//:skip&amp;\" classname=\"spaces\" time=\"0.000\">\n      <skipped/>"
+        ));
+        assert!(junit
+            .contains("<testcase name=\"//:fail&lt;1&gt;\" classname=\"spaces\" time=\"0.250\">"));
+        assert!(junit
+            .contains("fail&amp;.log\">building\nerror: a &lt; b &amp; &quot;c&quot;</failure>"));
+        assert!(!junit.contains("//:fail<1>"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
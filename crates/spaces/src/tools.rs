@@ -12,8 +12,9 @@ fn download_and_install(
     platform_archive: builtins::checkout::PlatformArchive,
     is_force_link: bool,
 ) -> anyhow::Result<()> {
-    let this_platform =
-        platform::Platform::get_platform().context(format_context!("Failed to get platform"))?;
+    // tools run on this machine so ignore any SPACES_PLATFORM override
+    let this_platform = platform::Platform::get_host_platform()
+        .context(format_context!("Failed to get platform"))?;
     let archive = match this_platform {
         platform::Platform::MacosX86_64 => platform_archive.macos_x86_64,
         platform::Platform::MacosAarch64 => platform_archive.macos_aarch64,
@@ -21,6 +22,8 @@ fn download_and_install(
         platform::Platform::LinuxAarch64 => platform_archive.linux_aarch64,
         platform::Platform::WindowsX86_64 => platform_archive.windows_x86_64,
         platform::Platform::WindowsAarch64 => platform_archive.windows_aarch64,
+        platform::Platform::LinuxRiscv64 => platform_archive.linux_riscv64,
+        platform::Platform::FreebsdX86_64 => platform_archive.freebsd_x86_64,
     };
     let store_path = workspace::get_checkout_store_path();
     let spaces_tools = workspace::get_spaces_tools_path(store_path.as_ref());
@@ -1,8 +1,8 @@
-use crate::inputs;
+use crate::{executor, inputs, rules, singleton};
 use anyhow::Context;
 use anyhow_source_location::{format_context, format_error};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
 
 pub const ENV_FILE_NAME: &str = "env.spaces.star";
@@ -17,9 +17,11 @@ const SPACES_CAPSULES_STATUS_NAME: &str = "status";
 const SPACES_CAPSULES_SYSROOT_NAME: &str = "sysroot";
 
 pub const SPACES_CAPSULES_INFO_NAME: &str = "capsules.spaces.json";
-const SETTINGS_FILE_NAME: &str = ".spaces/settings.spaces.json";
+pub const SETTINGS_FILE_NAME: &str = ".spaces/settings.spaces.json";
 const METRICS_FILE_NAME: &str = ".spaces/metrics.spaces.json";
-const SPACES_HOME_ENV_VAR: &str = "SPACES_HOME";
+const MAX_METRICS_RUNS: usize = 50;
+const MODULE_SEARCH_MAX_DEPTH: usize = 4;
+pub const SPACES_HOME_ENV_VAR: &str = "SPACES_HOME";
 pub const SPACES_ENV_IS_WORKSPACE_REPRODUCIBLE: &str = "SPACES_IS_WORKSPACE_REPRODUCIBLE";
 pub const SPACES_ENV_WORKSPACE_DIGEST: &str = "SPACES_WORKSPACE_DIGEST";
 pub const WORKSPACE_FILE_HEADER: &str = r#"
@@ -34,43 +36,109 @@ fn logger(progress: &mut printer::MultiProgressBar) -> logger::Logger<'_> {
     logger::Logger::new_progress(progress, "workspace".into())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum RuleStatus {
+    #[default]
+    Success,
+    Failure,
+    Skipped,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RuleMetrics {
-    elapsed_time: f64,
+    pub elapsed_time: f64,
+    #[serde(default)]
+    pub status: RuleStatus,
+    #[serde(default)]
+    pub duration_ms: u128,
+    #[serde(default)]
+    pub was_skipped: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_reason: Option<Arc<str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase: Option<rules::Phase>,
 }
 
+/// Metrics for one invocation of spaces
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunMetrics {
+    pub date: Arc<str>,
+    pub total_duration_ms: u128,
+    pub rules: HashMap<Arc<str>, RuleMetrics>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct RuleMetricsFile {
+    #[serde(default)]
+    pub runs: Vec<RunMetrics>,
+    // files written before runs were recorded have one untimed rule map per run
+    #[serde(default, skip_serializing)]
     metrics: Vec<HashMap<Arc<str>, RuleMetrics>>,
 }
 
 impl RuleMetricsFile {
-    pub fn update(
-        workspace: WorkspaceArc,
-    ) -> anyhow::Result<()> {
-        let workspace_path = workspace.read().get_absolute_path();
-        let metric_entry = workspace.read().rule_metrics.clone();
+    pub fn load(workspace_path: &str) -> anyhow::Result<Self> {
         let metrics_file = format!("{workspace_path}/{METRICS_FILE_NAME}");
-        let metrics_path = std::path::Path::new(metrics_file.as_str());
-        let metrics = if metrics_path.exists() {
-            let content = std::fs::read_to_string(metrics_file.as_str()).context(
-                format_context!("Failed to read metrics file {metrics_file}"),
-            )?;
-            let mut metrics_content: RuleMetricsFile = serde_json::from_str(content.as_str()).context(
-                format_context!("Failed to parse metrics file {metrics_file}"),
-            )?;
-            metrics_content.metrics.push(metric_entry);
-            metrics_content
-        } else {
-            RuleMetricsFile {
-                metrics: vec![metric_entry],
-            }
+        if !std::path::Path::new(metrics_file.as_str()).exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(metrics_file.as_str()).context(format_context!(
+            "Failed to read metrics file {metrics_file}"
+        ))?;
+        let mut metrics: Self = serde_json::from_str(content.as_str()).context(format_context!(
+            "Failed to parse metrics file {metrics_file}"
+        ))?;
+        metrics.migrate_legacy_runs();
+        Ok(metrics)
+    }
+
+    fn migrate_legacy_runs(&mut self) {
+        let legacy_runs = std::mem::take(&mut self.metrics)
+            .into_iter()
+            .map(|mut rules| {
+                for rule_metrics in rules.values_mut() {
+                    rule_metrics.duration_ms = (rule_metrics.elapsed_time * 1000.0) as u128;
+                }
+                RunMetrics {
+                    date: "".into(),
+                    total_duration_ms: 0,
+                    rules,
+                }
+            });
+        self.runs.splice(0..0, legacy_runs);
+        self.truncate_runs();
+    }
+
+    fn truncate_runs(&mut self) {
+        if self.runs.len() > MAX_METRICS_RUNS {
+            self.runs.drain(..self.runs.len() - MAX_METRICS_RUNS);
+        }
+    }
+
+    pub fn push_run(&mut self, run_metrics: RunMetrics) {
+        self.runs.push(run_metrics);
+        self.truncate_runs();
+    }
+
+    pub fn update(workspace: WorkspaceArc) -> anyhow::Result<()> {
+        let workspace_path = workspace.read().get_absolute_path();
+        let run_metrics = RunMetrics {
+            date: chrono::Local::now().to_rfc3339().into(),
+            total_duration_ms: workspace.read().start_time.elapsed().as_millis(),
+            rules: workspace.read().rule_metrics.clone(),
         };
+        let metrics_file = format!("{workspace_path}/{METRICS_FILE_NAME}");
+        let mut metrics = Self::load(workspace_path.as_ref()).context(format_context!(
+            "Failed to load metrics file {metrics_file}"
+        ))?;
+        metrics.push_run(run_metrics);
 
         let content = serde_json::to_string_pretty(&metrics)
             .context(format_context!("Failed to serialize metrics"))?;
 
-        std::fs::write(metrics_file.as_str(), content.as_str()).context(format_context!("Failed to write metrics file {metrics_file}"))?;
+        std::fs::write(metrics_file.as_str(), content.as_str()).context(format_context!(
+            "Failed to write metrics file {metrics_file}"
+        ))?;
 
         Ok(())
     }
@@ -80,6 +148,9 @@ impl RuleMetricsFile {
 pub struct Settings {
     pub store_path: Arc<str>,
     order: Vec<Arc<str>>,
+    /// The version of spaces that created the workspace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spaces_version: Option<Arc<str>>,
 }
 
 impl Settings {
@@ -109,6 +180,58 @@ impl Settings {
     }
 }
 
+#[derive(Debug)]
+enum VersionSkew {
+    None,
+    Unknown(String),
+    Older(String),
+}
+
+/// Compares the running spaces version with the version that created the workspace. Running
+/// an older version is an error under `--strict`.
+fn get_version_skew(
+    created_version: &str,
+    current_version: &str,
+    is_strict: bool,
+) -> anyhow::Result<VersionSkew> {
+    let (Ok(created), Ok(current)) = (
+        created_version.parse::<semver::Version>(),
+        current_version.parse::<semver::Version>(),
+    ) else {
+        return Ok(VersionSkew::Unknown(format!(
+            "Failed to compare {created_version} with {current_version}"
+        )));
+    };
+
+    if current < created {
+        let message = format!(
+            "spaces {current_version} is older than spaces {created_version} which created this workspace. Features of the newer version may not work"
+        );
+        if is_strict {
+            return Err(format_error!("{message}"));
+        }
+        return Ok(VersionSkew::Older(message));
+    }
+    Ok(VersionSkew::None)
+}
+
+fn check_spaces_version(
+    progress: &mut printer::MultiProgressBar,
+    created_version: &str,
+) -> anyhow::Result<()> {
+    let version_skew = get_version_skew(
+        created_version,
+        env!("CARGO_PKG_VERSION"),
+        singleton::get_is_strict(),
+    )?;
+    match version_skew {
+        VersionSkew::None => {}
+        VersionSkew::Unknown(message) => logger(progress).debug(message.as_str()),
+        VersionSkew::Older(message) => logger(progress).warning(message.as_str()),
+    }
+    Ok(())
+}
+
 pub fn get_short_digest(digest: &str) -> Arc<str> {
     digest[0..8].into()
 }
@@ -133,6 +256,35 @@ pub fn is_rules_module(path: &str) -> bool {
     path.ends_with(SPACES_MODULE_NAME)
 }
 
+/// Returns the `spaces.star` modules in `repo_path` relative to `repo_path`.
+///
+/// Searches at most `MODULE_SEARCH_MAX_DEPTH` directories deep and skips hidden directories.
+pub fn find_rules_modules(repo_path: &str) -> anyhow::Result<Vec<Arc<str>>> {
+    let mut modules = Vec::new();
+    let walkdir = walkdir::WalkDir::new(repo_path)
+        .max_depth(MODULE_SEARCH_MAX_DEPTH)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+        });
+
+    for entry in walkdir {
+        let entry = entry.context(format_context!("Failed to read directory {repo_path}"))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.path();
+        if is_rules_module(path.to_string_lossy().as_ref()) {
+            let relative_path = path.strip_prefix(repo_path).context(format_context!(
+                "Failed to strip prefix {repo_path} from {path:?}"
+            ))?;
+            modules.push(relative_path.to_string_lossy().into());
+        }
+    }
+    modules.sort();
+    Ok(modules)
+}
+
 pub fn get_workspace_path(workspace_path: &str, current_path: &str, target_path: &str) -> Arc<str> {
     if target_path.starts_with("//") {
         format!("{workspace_path}/{target_path}").into()
@@ -147,6 +299,9 @@ pub fn get_workspace_path(workspace_path: &str, current_path: &str, target_path:
 }
 
 pub fn get_checkout_store_path() -> Arc<str> {
+    if let Some(store_path) = singleton::get_store_path() {
+        return store_path;
+    }
     if let Ok(spaces_home) = std::env::var(SPACES_HOME_ENV_VAR) {
         return format!("{}/.spaces/store", spaces_home).into();
     }
@@ -177,6 +332,11 @@ pub fn get_inputs_path() -> &'static str {
     "build/workspace.inputs.spaces"
 }
 
+/// Output digests are kept apart from the inputs so existing inputs files still decode
+pub fn get_outputs_path() -> &'static str {
+    "build/workspace.outputs.spaces"
+}
+
 pub fn get_changes_path() -> &'static str {
     "build/workspace.changes.spaces"
 }
@@ -190,21 +350,37 @@ pub struct Workspace {
     pub digest: Arc<str>,                   // set at startup
     pub store_path: Option<Arc<str>>,       // set at startup
     pub locks: HashMap<Arc<str>, Arc<str>>, // set during eval
+    pub lock_drift: Vec<Arc<str>>,          // set during eval with --frozen
     pub env: environment::Environment,      // set during eval
     #[allow(dead_code)]
     pub new_branch_name: Option<Arc<str>>, // set during eval - not used
     changes: changes::Changes,              // modified during run
     inputs: inputs::Inputs,                 // modified during run
-    pub updated_assets: HashSet<Arc<str>>,  // used by assets to keep track of exclusive access
+    outputs: inputs::Inputs,                // modified during run
+    // update_asset values by destination and rule, merged each time a rule updates the file
+    pub updated_assets: HashMap<Arc<str>, BTreeMap<Arc<str>, executor::asset::UpdateAsset>>,
     pub rule_metrics: HashMap<Arc<str>, RuleMetrics>, // used to keep track of rule metrics
+    pub start_time: std::time::Instant,               // set at startup
 }
 
 impl Workspace {
-    pub fn update_rule_metrics(&mut self, rule_name: &str, elapsed_time: std::time::Duration) {
+    pub fn update_rule_metrics(
+        &mut self,
+        rule_name: &str,
+        phase: rules::Phase,
+        elapsed_time: std::time::Duration,
+        status: RuleStatus,
+        skip_reason: Option<Arc<str>>,
+    ) {
         self.rule_metrics.insert(
             rule_name.into(),
             RuleMetrics {
                 elapsed_time: elapsed_time.as_secs_f64(),
+                status,
+                duration_ms: elapsed_time.as_millis(),
+                was_skipped: status == RuleStatus::Skipped,
+                skip_reason,
+                phase: Some(phase),
             },
         );
     }
@@ -232,7 +408,7 @@ impl Workspace {
         false
     }
 
-    fn find_workspace_root(current_working_directory: &str) -> anyhow::Result<Arc<str>> {
+    pub fn find_workspace_root(current_working_directory: &str) -> anyhow::Result<Arc<str>> {
         let mut current_directory = current_working_directory.to_owned();
         loop {
             let workspace_path = format!("{}/{}", current_directory, ENV_FILE_NAME);
@@ -301,6 +477,10 @@ impl Workspace {
         let mut store_path = None;
         if let Ok(load_order) = Settings::load(absolute_path.as_ref()) {
             logger(&mut progress).trace("Loading modules from sync order");
+            if let Some(spaces_version) = load_order.spaces_version.as_ref() {
+                check_spaces_version(&mut progress, spaces_version.as_ref())
+                    .context(format_context!("Failed to check the workspace spaces version"))?;
+            }
             store_path = Some(load_order.store_path);
             for module in load_order.order {
                 if is_rules_module(module.as_ref()) {
@@ -373,6 +553,8 @@ impl Workspace {
             "Failed to create log folder {log_directory}",
         ))?;
 
+        git::set_log_directory(log_directory.clone());
+
         std::fs::create_dir_all(build_directory())
             .context(format_context!("Failed to create build directory"))?;
 
@@ -396,22 +578,54 @@ impl Workspace {
             digest: workspace_digest,
             store_path,
             locks: HashMap::new(),
+            lock_drift: Vec::new(),
             env,
             new_branch_name: None,
             changes,
-            updated_assets: HashSet::new(),
+            updated_assets: HashMap::new(),
             inputs: inputs::Inputs::new(get_inputs_path()),
+            outputs: inputs::Inputs::new(get_outputs_path()),
             rule_metrics: HashMap::new(),
+            start_time: std::time::Instant::now(),
         })
     }
 
     pub fn set_env(&mut self, env: environment::Environment) {
         self.env = env;
+        self.register_secrets();
+    }
+
+    /// Redacts the values of `secret_vars` in the logs as soon as the env is loaded
+    fn register_secrets(&self) {
+        let Some(secret_vars) = self.env.secret_vars.as_ref() else {
+            return;
+        };
+        // values that can't be resolved yet are registered when the rule executes
+        let Ok(vars) = self.env.get_vars() else {
+            return;
+        };
+        for secret_var in secret_vars {
+            if let Some(value) = vars.get(secret_var) {
+                logger::add_secret(value.as_ref());
+            }
+        }
     }
 
     pub fn update_env(&mut self, env: environment::Environment) -> anyhow::Result<()> {
         self.env.vars.extend(env.vars);
         self.env.paths.extend(env.paths);
+        if let Some(secret_vars) = env.secret_vars {
+            self.env
+                .secret_vars
+                .get_or_insert_with(Vec::new)
+                .extend(secret_vars);
+        }
+        if let Some(keep_duplicates_vars) = env.keep_duplicates_vars {
+            self.env
+                .keep_duplicates_vars
+                .get_or_insert_with(Vec::new)
+                .extend(keep_duplicates_vars);
+        }
         if let Some(inherited_vars) = env.inherited_vars {
             if let Some(existing_inherited_vars) = self.env.inherited_vars.as_mut() {
                 existing_inherited_vars.extend(inherited_vars.clone());
@@ -427,6 +641,7 @@ impl Workspace {
                 self.env.system_paths = Some(system_paths);
             }
         }
+        self.register_secrets();
         Ok(())
     }
 
@@ -472,9 +687,10 @@ impl Workspace {
         &mut self,
         progress: &mut printer::MultiProgressBar,
         inputs: &HashSet<Arc<str>>,
+        skip_folders: &[Arc<str>],
     ) -> anyhow::Result<()> {
         self.changes
-            .update_from_inputs(progress, inputs)
+            .update_from_inputs(progress, inputs, skip_folders)
             .context(format_context!("Failed to update workspace changes"))?;
 
         Ok(())
@@ -497,8 +713,17 @@ impl Workspace {
         progress: &mut printer::MultiProgressBar,
         seed: &str,
         globs: &HashSet<Arc<str>>,
+        skip_folders: &[Arc<str>],
     ) -> anyhow::Result<Arc<str>> {
-        self.changes.get_digest(progress, seed, globs)
+        self.changes.get_digest(progress, seed, globs, skip_folders)
+    }
+
+    pub fn inspect_rule_inputs(
+        &self,
+        globs: &HashSet<Arc<str>>,
+        skip_folders: &[Arc<str>],
+    ) -> Vec<Arc<str>> {
+        self.changes.inspect_inputs(globs, skip_folders)
     }
 
     pub fn get_short_digest(&self) -> Arc<str> {
@@ -555,9 +780,10 @@ impl Workspace {
         rule_name: &str,
         seed: &str,
         inputs: &HashSet<Arc<str>>,
+        skip_folders: &[Arc<str>],
     ) -> anyhow::Result<Option<Arc<str>>> {
         let digest = self
-            .get_rule_inputs_digest(progress, seed, inputs)
+            .get_rule_inputs_digest(progress, seed, inputs, skip_folders)
             .context(format_context!("Failed to get digest for rule {rule_name}"))?;
         self.inputs.is_changed(rule_name, digest)
     }
@@ -566,8 +792,114 @@ impl Workspace {
         self.inputs.save_digest(rule, digest);
     }
 
+    pub fn clear_rule_digest(&mut self, rule: &str) {
+        self.inputs.remove_digest(rule);
+        self.outputs.remove_digest(rule);
+    }
+
+    pub fn is_rule_outputs_changed(&self, rule_name: &str, digest: &str) -> bool {
+        !matches!(self.outputs.is_changed(rule_name, digest.into()), Ok(None))
+    }
+
+    pub fn update_rule_outputs_digest(&mut self, rule: &str, digest: Arc<str>) {
+        self.outputs.save_digest(rule, digest);
+    }
+
     pub fn save_inputs(&self) -> anyhow::Result<()> {
-        let inputs_path = get_inputs_path();
-        self.inputs.save(inputs_path)
+        self.inputs
+            .save(get_inputs_path())
+            .context(format_context!("Failed to save inputs"))?;
+        self.outputs
+            .save(get_outputs_path())
+            .context(format_context!("Failed to save outputs"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_rules_modules_by_depth_and_skips_hidden() {
+        let root = std::env::temp_dir().join(format!("spaces_modules_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for directory in ["", "b", "a/c", "a/b/c", "a/b/c/d", ".git", "a/.hidden"] {
+            let directory = root.join(directory);
+            std::fs::create_dir_all(&directory).unwrap();
+            std::fs::write(directory.join(SPACES_MODULE_NAME), "").unwrap();
+        }
+        std::fs::write(root.join("a").join("other.star"), "").unwrap();
+
+        let modules = find_rules_modules(root.to_string_lossy().as_ref()).unwrap();
+        let modules: Vec<&str> = modules.iter().map(|module| module.as_ref()).collect();
+        // a/b/c/d/spaces.star is deeper than MODULE_SEARCH_MAX_DEPTH
+        assert_eq!(
+            modules,
+            vec![
+                "a/b/c/spaces.star",
+                "a/c/spaces.star",
+                "b/spaces.star",
+                "spaces.star",
+            ]
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn metrics_keep_legacy_runs_and_cap_the_history() {
+        let root = std::env::temp_dir().join(format!("spaces_metrics_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join(".spaces")).unwrap();
+        std::fs::write(
+            root.join(METRICS_FILE_NAME),
+            r#"{"metrics": [{"//:build": {"elapsed_time": 1.5}}]}"#,
+        )
+        .unwrap();
+
+        let mut metrics = RuleMetricsFile::load(root.to_string_lossy().as_ref()).unwrap();
+        assert_eq!(metrics.runs.len(), 1);
+        assert_eq!(metrics.runs[0].rules["//:build"].duration_ms, 1500);
+
+        for run in 0..MAX_METRICS_RUNS {
+            metrics.push_run(RunMetrics {
+                date: format!("run{run}").into(),
+                total_duration_ms: run as u128,
+                rules: HashMap::new(),
+            });
+        }
+        assert_eq!(metrics.runs.len(), MAX_METRICS_RUNS);
+        assert_eq!(metrics.runs[0].date.as_ref(), "run0");
+        assert_eq!(
+            metrics.runs.last().unwrap().total_duration_ms,
+            MAX_METRICS_RUNS as u128 - 1
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn version_skew_warns_when_older_and_fails_when_strict() {
+        for (created, current) in [("1.2.0", "1.2.0"), ("1.2.0", "1.3.0"), ("1.2.0", "2.0.0")] {
+            assert!(matches!(
+                get_version_skew(created, current, true).unwrap(),
+                VersionSkew::None
+            ));
+        }
+
+        let skew = get_version_skew("1.3.0", "1.2.9", false).unwrap();
+        let VersionSkew::Older(message) = skew else {
+            panic!("expected an older version");
+        };
+        assert!(message.contains("1.2.9") && message.contains("1.3.0"));
+
+        let error = get_version_skew("1.3.0", "1.2.9", true).unwrap_err();
+        assert!(error.to_string().contains("is older than spaces 1.3.0"));
+
+        // an unparsable version is only logged, even with --strict
+        assert!(matches!(
+            get_version_skew("not-a-version", "1.2.0", true).unwrap(),
+            VersionSkew::Unknown(_)
+        ));
     }
 }
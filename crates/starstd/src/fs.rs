@@ -5,6 +5,14 @@ use starlark::environment::GlobalsBuilder;
 use starlark::values::none::NoneType;
 use starlark::values::{Heap, Value};
 
+const GLOB_EXAMPLE: &str = r#"sources = fs.glob(
+    include = ["src/**/*.c", "include/**/*.h"],
+    exclude = ["src/tests/**"],
+)
+
+for source in sources:
+    print(source)"#;
+
 pub const FUNCTIONS: &[Function] = &[
     Function {
         name: "write_string_to_file",
@@ -142,8 +150,105 @@ pub const FUNCTIONS: &[Function] = &[
         }],
         example: None,
     },
+    Function {
+        name: "glob",
+        description: "Returns a sorted list of files that match the include globs and do not match the exclude globs. Uses the same matching as rule `inputs`.",
+        return_type: "[str]",
+        args: &[
+            Arg {
+                name: "include",
+                description: "list of globs to include relative to the workspace root (absolute paths and `..` are not allowed)",
+                dict: &[],
+            },
+            Arg {
+                name: "exclude",
+                description: "optional list of globs to exclude",
+                dict: &[],
+            },
+        ],
+        example: Some(GLOB_EXAMPLE),
+    },
+    Function {
+        name: "read_directory_tree",
+        description: "Returns a list of dicts `{path, is_dir, is_symlink, size}` for the entries under a directory. The path must be inside the workspace.",
+        return_type: "[dict]",
+        args: &[
+            Arg {
+                name: "path",
+                description: "path relative to the workspace root",
+                dict: &[],
+            },
+            Arg {
+                name: "recursive",
+                description: "optionally include entries in sub-directories (default is False)",
+                dict: &[],
+            },
+            Arg {
+                name: "max_depth",
+                description: "optional maximum depth to recurse when `recursive` is True",
+                dict: &[],
+            },
+        ],
+        example: None,
+    },
 ];
 
+// walk from the literal directory prefix of the glob rather than the whole workspace
+fn get_glob_walk_root(glob: &str) -> String {
+    let mut root = Vec::new();
+    let components: Vec<&str> = glob.split('/').collect();
+    for component in components.iter().take(components.len().saturating_sub(1)) {
+        if component.contains(['*', '?', '[', '{']) {
+            break;
+        }
+        root.push(*component);
+    }
+    if root.is_empty() {
+        ".".to_string()
+    } else {
+        root.join("/")
+    }
+}
+
+/// Files under `workspace_root` that match `include` and not `exclude`, relative to the root
+fn get_glob_files(
+    workspace_root: &std::path::Path,
+    include: &[String],
+    exclude: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let mut globs = std::collections::HashSet::new();
+    for pattern in include.iter() {
+        globs.insert(format!("+{pattern}").into());
+    }
+    for pattern in exclude.iter() {
+        globs.insert(format!("-{pattern}").into());
+    }
+
+    let mut result = std::collections::BTreeSet::new();
+    for pattern in include.iter() {
+        if std::path::Path::new(pattern).is_absolute()
+            || pattern.split('/').any(|component| component == "..")
+        {
+            return Err(anyhow::anyhow!("glob {pattern} must be relative to the workspace root"));
+        }
+        let walk_root = workspace_root.join(get_glob_walk_root(pattern));
+        for entry in walkdir::WalkDir::new(walk_root).into_iter().flatten() {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(relative_path) = entry.path().strip_prefix(workspace_root) else {
+                continue;
+            };
+            let path = relative_path.to_string_lossy();
+            if changes::glob::match_globs(&globs, path.as_ref()) {
+                result.insert(path.to_string());
+            }
+        }
+    }
+
+    Ok(result.into_iter().collect())
+}
+
 // This defines the function that is visible to Starlark
 #[starlark_module]
 pub fn globals(builder: &mut GlobalsBuilder) {
@@ -289,4 +394,98 @@ pub fn globals(builder: &mut GlobalsBuilder) {
 
         Ok(result)
     }
+
+    fn glob(
+        #[starlark(require = named)] include: starlark::values::Value,
+        #[starlark(require = named)] exclude: Option<starlark::values::Value>,
+    ) -> anyhow::Result<Vec<String>> {
+        let include: Vec<String> = serde_json::from_value(include.to_json_value()?)
+            .context(format_context!("include must be a list of strings"))?;
+        let exclude: Vec<String> = if let Some(exclude) = exclude {
+            serde_json::from_value(exclude.to_json_value()?)
+                .context(format_context!("exclude must be a list of strings"))?
+        } else {
+            Vec::new()
+        };
+
+        // like the other fs functions, the current directory is the workspace root
+        let workspace_root = std::env::current_dir()
+            .context(format_context!("Failed to get the workspace root"))?;
+        get_glob_files(workspace_root.as_path(), &include, &exclude)
+    }
+
+    fn read_directory_tree<'v>(
+        #[starlark(require = pos)] path: &str,
+        #[starlark(require = named)] recursive: Option<bool>,
+        #[starlark(require = named)] max_depth: Option<u32>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        let workspace_root = std::env::current_dir()
+            .and_then(|directory| directory.canonicalize())
+            .context(format_context!("Failed to get the workspace root"))?;
+        let directory = std::path::Path::new(path)
+            .canonicalize()
+            .context(format_context!("Failed to read directory {path}"))?;
+        if !directory.starts_with(&workspace_root) {
+            return Err(anyhow::anyhow!(
+                "{path} is outside the workspace root {workspace_root:?}"
+            ));
+        }
+
+        let max_depth = if recursive.unwrap_or(false) {
+            max_depth.map(|depth| depth as usize).unwrap_or(usize::MAX)
+        } else {
+            1
+        };
+
+        let mut entries = Vec::new();
+        for entry in walkdir::WalkDir::new(path)
+            .min_depth(1)
+            .max_depth(max_depth)
+            .sort_by_file_name()
+        {
+            let entry = entry.context(format_context!("Failed to read directory {path}"))?;
+            let metadata = entry
+                .metadata()
+                .context(format_context!("Failed to read metadata for {:?}", entry.path()))?;
+            entries.push(serde_json::json!({
+                "path": entry.path().to_string_lossy(),
+                "is_dir": entry.file_type().is_dir(),
+                "is_symlink": entry.path_is_symlink(),
+                "size": metadata.len(),
+            }));
+        }
+
+        Ok(heap.alloc(serde_json::Value::Array(entries)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_files_are_relative_to_the_workspace_root() {
+        let root = std::env::temp_dir().join(format!("starstd-glob-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src/gen")).unwrap();
+        std::fs::write(root.join("src/main.c"), "").unwrap();
+        std::fs::write(root.join("src/gen/table.c"), "").unwrap();
+        std::fs::write(root.join("README.md"), "").unwrap();
+
+        let to_strings = |globs: &[&str]| -> Vec<String> {
+            globs.iter().map(|glob| glob.to_string()).collect()
+        };
+
+        let include = to_strings(&["src/**/*.c"]);
+        let exclude = to_strings(&["src/gen/**"]);
+        let files = get_glob_files(&root, &include, &exclude).unwrap();
+        assert_eq!(files, vec!["src/main.c".to_string()]);
+
+        let files = get_glob_files(&root, &to_strings(&["**/*.md"]), &[]).unwrap();
+        assert_eq!(files, vec!["README.md".to_string()]);
+
+        assert!(get_glob_files(&root, &to_strings(&["../**/*.c"]), &[]).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
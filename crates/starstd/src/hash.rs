@@ -2,6 +2,8 @@ use crate::{Arg, Function};
 use anyhow::Context;
 use anyhow_source_location::format_context;
 use starlark::environment::GlobalsBuilder;
+use std::collections::HashSet;
+use std::sync::Arc;
 
 pub const FUNCTIONS: &[Function] = &[
     Function {
@@ -18,6 +20,37 @@ pub const FUNCTIONS: &[Function] = &[
         ],
         example: None,
     },
+    Function {
+        name: "file",
+        description: "Computes the blake3 digest of the contents of a file and returns it as a hex string.",
+        return_type: "String",
+        args: &[
+            Arg {
+                name: "path",
+                description: "path to the file",
+                dict: &[],
+            },
+        ],
+        example: None,
+    },
+    Function {
+        name: "directory",
+        description: "Computes the blake3 digest of all files in a directory tree (relative paths and contents) and returns it as a hex string.",
+        return_type: "String",
+        args: &[
+            Arg {
+                name: "path",
+                description: "path to the directory",
+                dict: &[],
+            },
+            Arg {
+                name: "globs",
+                description: "optional list of globs relative to `path` prefixed with `+` to include and `-` to exclude",
+                dict: &[],
+            },
+        ],
+        example: None,
+    },
 ];
 
 // This defines the function that is visible to Starlark
@@ -28,4 +61,32 @@ pub fn globals(builder: &mut GlobalsBuilder) {
         let digest = sha256::digest(file_contents);
         Ok(digest)
     }
+
+    fn file(path: &str) -> anyhow::Result<String> {
+        let file_path = std::path::Path::new(path);
+        if !file_path.is_file() {
+            return Err(anyhow::anyhow!("{path} is not a file"));
+        }
+        let digest = changes::get_file_digest(file_path).context(format_context!("{path}"))?;
+        Ok(digest.to_string())
+    }
+
+    fn directory(
+        #[starlark(require = pos)] path: &str,
+        #[starlark(require = named)] globs: Option<starlark::values::Value>,
+    ) -> anyhow::Result<String> {
+        let globs: Option<HashSet<Arc<str>>> = if let Some(globs) = globs {
+            let globs: Vec<String> = serde_json::from_value(globs.to_json_value()?)
+                .context(format_context!("globs must be a list of strings"))?;
+            let globs: HashSet<Arc<str>> = globs.into_iter().map(|glob| glob.into()).collect();
+            changes::glob::validate(&globs).context(format_context!("invalid globs"))?;
+            Some(globs)
+        } else {
+            None
+        };
+
+        let digest = changes::get_directory_digest(std::path::Path::new(path), globs.as_ref())
+            .context(format_context!("{path}"))?;
+        Ok(digest.to_string())
+    }
 }
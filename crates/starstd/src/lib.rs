@@ -32,11 +32,14 @@ pub const fn get_rule_argument() -> Arg {
         description: "dict",
         dict: &[
             ("name", "rule name as string"),
-            ("deps", "list of dependencies"),
+            ("deps", "list of dependencies, run rule entries with `*` are globs that match other run rules (e.g. `tools/*:build`)"),
             ("platforms", "optional list of platforms to run on. If not provided, rule will run on all platforms. See above for details"),
             ("type", "Checkout|Optional|Setup|Run: see above for details"),
             ("type", "Setup|Run (default)|Optional"),
-            ("help", "Optional help text show with `spaces evaluate`"),
+            ("description", "Optional one line summary shown with `spaces evaluate` (default is the first line of help)"),
+            ("help", "Optional help text shown with the rule details (`spaces --verbosity=debug evaluate`)"),
+            ("skip_folders", "Optional list of folder names (e.g. `target`) that are not scanned for this rule's inputs"),
+            ("artifacts", "Optional list of globs for the files `spaces run --export-artifacts` collects (default is the outputs)"),
         ],
     }
 }
@@ -17,28 +17,111 @@ pub struct Exec {
     pub stdin: Option<String>,
 }
 
-pub const FUNCTIONS: &[Function] = &[Function {
-    name: "exec",
-    description: "Executes a process",
-    return_type: "dict # with members `status`, `stdout`, and `stderr`",
-    args: &[
-        Arg {
-            name: "exec",
-            description: "dict with members",
-            dict: &[
-                ("command", "name of the command to execute"),
-                ("args", "optional list of arguments"),
-                ("env", "optional dict of environment variables"),
-                (
-                    "working_directory",
-                    "optional working directory (default is the workspace)",
-                ),
-                ("stdin", "optional string to pipe to the process stdin"),
-            ],
+pub const FUNCTIONS: &[Function] = &[
+    Function {
+        name: "exec",
+        description: "Executes a process",
+        return_type: "dict # with members `status`, `stdout`, and `stderr`",
+        args: &[
+            Arg {
+                name: "exec",
+                description: "dict with members",
+                dict: &[
+                    ("command", "name of the command to execute"),
+                    ("args", "optional list of arguments"),
+                    ("env", "optional dict of environment variables"),
+                    (
+                        "working_directory",
+                        "optional working directory (default is the workspace)",
+                    ),
+                    ("stdin", "optional string to pipe to the process stdin"),
+                ],
+            },
+        ],
+        example: None,
+    },
+    Function {
+        name: "run_capture",
+        description: "Runs a command while the script is evaluated (not as a rule) and captures its output. The process starts with an empty environment so the result only depends on what is passed in `env`. Host tool output can differ between machines, so branching on it makes the workspace less reproducible.",
+        return_type: "dict # with members `status`, `stdout`, and `stderr`",
+        args: &[
+            Arg {
+                name: "command",
+                description: "name of the command to execute",
+                dict: &[],
+            },
+            Arg {
+                name: "args",
+                description: "optional list of arguments",
+                dict: &[],
+            },
+            Arg {
+                name: "env",
+                description: "optional dict of environment variables (default is empty, include `PATH` if the command needs it)",
+                dict: &[],
+            },
+            Arg {
+                name: "working_directory",
+                description: "optional working directory (default is the workspace)",
+                dict: &[],
+            },
+        ],
+        example: Some(RUN_CAPTURE_EXAMPLE),
+    },
+];
+
+const RUN_CAPTURE_EXAMPLE: &str = r#"result = process.run_capture("git", args = ["--version"])
+if result["status"] == 0 and "2.4" in result["stdout"]:
+    print("recent git")"#;
+
+fn capture_output(
+    mut command: Command,
+    stdin: Option<String>,
+) -> anyhow::Result<serde_json::Value> {
+    if stdin.is_some() {
+        // send stdin to the process on standard input
+        command.stdin(std::process::Stdio::piped());
+    }
+
+    command.stdout(std::process::Stdio::piped());
+    command.stderr(std::process::Stdio::piped());
+
+    let invoke_command = command.get_program().to_string_lossy().to_string();
+    let mut child = command
+        .spawn()
+        .context(format_context!("Failed to spawn child process {invoke_command}"))?;
+
+    if let Some(stdin) = stdin {
+        use std::io::Write;
+        let child_stdin = child.stdin.as_mut().unwrap();
+        child_stdin
+            .write_all(stdin.as_bytes())
+            .context(format_context!("Failed to write to stdin"))?;
+    }
+
+    let output_result = child.wait_with_output();
+    let (status, stdout, stderr) = match output_result {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            (
+                output.status.code().unwrap_or(1),
+                stdout.to_string(),
+                stderr.to_string(),
+            )
         }
-    ],
-    example: None,
-}];
+        Err(e) => (1, String::new(), e.to_string()),
+    };
+
+    let mut result_map = serde_json::Map::new();
+    result_map.insert(
+        "status".to_string(),
+        serde_json::Value::Number(status.into()),
+    );
+    result_map.insert("stdout".to_string(), serde_json::Value::String(stdout));
+    result_map.insert("stderr".to_string(), serde_json::Value::String(stderr));
+    Ok(serde_json::Value::Object(result_map))
+}
 
 // This defines the functions that are visible to Starlark
 #[starlark_module]
@@ -47,10 +130,6 @@ pub fn globals(builder: &mut GlobalsBuilder) {
         let exec: Exec = serde_json::from_value(exec.to_json_value()?)
             .context(format_context!("bad options for exec"))?;
 
-        let exec_stdin = exec.stdin;
-
-        let invoke_command = exec.command.clone();
-
         let mut command = Command::new(exec.command);
         for arg in exec.args.unwrap_or_default() {
             command.arg(arg);
@@ -60,58 +139,43 @@ pub fn globals(builder: &mut GlobalsBuilder) {
             command.env(name, value);
         }
 
-        if exec_stdin.is_some() {
-            // send stdin to the process on standard input
-            command.stdin(std::process::Stdio::piped());
-        }
-
-        command.stdout(std::process::Stdio::piped());
-        command.stderr(std::process::Stdio::piped());
         if let Some(working_directory) = exec.working_directory {
             command.current_dir(working_directory);
         }
 
-        let child_result = command.spawn();
-
-        if let Ok(mut child) = child_result {
-            if let Some(stdin) = exec_stdin {
-                use std::io::Write;
-                let child_stdin = child.stdin.as_mut().unwrap();
-                child_stdin
-                    .write_all(stdin.as_bytes())
-                    .context(format_context!("Failed to write to stdin"))?;
-            }
-
-            let output_result = child.wait_with_output();
-            let (status, stdout, stderr) = match output_result {
-                Ok(output) => {
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    (
-                        output.status.code().unwrap_or(1),
-                        stdout.to_string(),
-                        stderr.to_string(),
-                    )
-                }
-                Err(e) => (1, String::new(), e.to_string()),
-            };
-
-            let mut result_map = serde_json::Map::new();
-            result_map.insert(
-                "status".to_string(),
-                serde_json::Value::Number(status.into()),
-            );
-            result_map.insert(
-                "stdout".to_string(),
-                serde_json::Value::String(stdout.to_string()),
-            );
-            result_map.insert(
-                "stderr".to_string(),
-                serde_json::Value::String(stderr.to_string()),
-            );
-            Ok(heap.alloc(serde_json::Value::Object(result_map)))
+        let result = capture_output(command, exec.stdin)?;
+        Ok(heap.alloc(result))
+    }
+
+    fn run_capture<'v>(
+        #[starlark(require = pos)] command: &str,
+        #[starlark(require = named)] args: Option<Value>,
+        #[starlark(require = named)] env: Option<Value>,
+        #[starlark(require = named)] working_directory: Option<&str>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        let args: Vec<String> = if let Some(args) = args {
+            serde_json::from_value(args.to_json_value()?)
+                .context(format_context!("args must be a list of strings"))?
+        } else {
+            Vec::new()
+        };
+        let env: HashMap<String, String> = if let Some(env) = env {
+            serde_json::from_value(env.to_json_value()?)
+                .context(format_context!("env must be a dict of strings"))?
         } else {
-            Err(child_result.unwrap_err()).context(format_context!("Failed to spawn child process {invoke_command}"))
+            HashMap::new()
+        };
+
+        let mut command = Command::new(command);
+        command.args(args);
+        command.env_clear();
+        command.envs(env);
+        if let Some(working_directory) = working_directory {
+            command.current_dir(working_directory);
         }
+
+        let result = capture_output(command, None)?;
+        Ok(heap.alloc(result))
     }
 }